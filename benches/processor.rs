@@ -0,0 +1,24 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use chip8_rs::bench::{self, WORKLOADS};
+use chip8_rs::engine::{self, Engine};
+
+// instructions per iteration
+const INSTRUCTIONS: usize = 100_000;
+
+fn engines(c: &mut Criterion) {
+    for workload in &WORKLOADS {
+        let mut group = c.benchmark_group(workload.name);
+        group.throughput(Throughput::Elements(INSTRUCTIONS as u64));
+        for name in &engine::NAMES {
+            let engine = Engine::from_name(name).unwrap();
+            group.bench_function(BenchmarkId::from_parameter(name), |b| {
+                b.iter(|| bench::run(workload.rom, engine, INSTRUCTIONS))
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, engines);
+criterion_main!(benches);
@@ -0,0 +1,154 @@
+#ifndef CHIP8_H
+#define CHIP8_H
+
+/* Generated by cbindgen from capi/src/lib.rs, don't edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Width of the screen returned by chip8_screen, in pixels.
+ */
+#define CHIP8_SCREEN_WIDTH 64
+
+/**
+ * Height of the screen returned by chip8_screen, in pixels.
+ */
+#define CHIP8_SCREEN_HEIGHT 32
+
+/**
+ * Result of the calls, which can fail.
+ */
+typedef enum {
+  CHIP8_STATUS_OK = 0,
+  CHIP8_STATUS_NULL_POINTER,
+  CHIP8_STATUS_INVALID_ARGUMENT,
+  CHIP8_STATUS_ROM_TOO_LARGE,
+  CHIP8_STATUS_OUT_OF_BOUNDS,
+  CHIP8_STATUS_INVALID_STATE,
+  /**
+   * The rom did something impossible, like returning with an empty stack or
+   * reading past the end of memory. Reset or load a state to go on.
+   */
+  CHIP8_STATUS_CRASHED,
+} Chip8Status;
+
+/**
+ * An emulator instance, created by chip8_new and freed by chip8_free.
+ */
+typedef struct Chip8 Chip8;
+
+/**
+ * The cpu registers, as read by chip8_get_registers.
+ */
+typedef struct {
+  uint8_t v[16];
+  uint32_t i;
+  uint32_t pc;
+  uint8_t delay_timer;
+  uint8_t sound_timer;
+} Chip8Registers;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * A new instance with the default quirks and nothing loaded.
+ */
+Chip8 *chip8_new(void);
+
+/**
+ * Free an instance, null is ignored.
+ */
+void chip8_free(Chip8 *chip8);
+
+/**
+ * Emulate the quirks, memory and font of "chip8", "schip", "xochip" or "eti660".
+ * Call it before chip8_load_rom, as it clears the memory.
+ */
+Chip8Status chip8_set_platform(Chip8 *chip8, const char *name);
+
+/**
+ * Instructions run by chip8_run_frame, 10 by default.
+ */
+Chip8Status chip8_set_tickrate(Chip8 *chip8, size_t cycles_per_frame);
+
+/**
+ * Reset the processor and load a rom, the bytes get copied.
+ */
+Chip8Status chip8_load_rom(Chip8 *chip8, const uint8_t *data, size_t len);
+
+/**
+ * Start the loaded rom over.
+ */
+Chip8Status chip8_reset(Chip8 *chip8);
+
+/**
+ * Run a number of instructions without ticking the timers. redraw is set to
+ * whether the screen changed, it may be null.
+ */
+Chip8Status chip8_step(Chip8 *chip8, size_t cycles, bool *redraw);
+
+/**
+ * Run the instructions of a 60Hz frame and tick the timers. redraw is set to
+ * whether the screen changed, it may be null.
+ */
+Chip8Status chip8_run_frame(Chip8 *chip8, bool *redraw);
+
+/**
+ * Press or release a key of the hex keypad, 0 to 15.
+ */
+Chip8Status chip8_set_key(Chip8 *chip8, uint8_t key, bool pressed);
+
+/**
+ * CHIP8_SCREEN_WIDTH * CHIP8_SCREEN_HEIGHT pixels, row by row. A pixel is 0 when
+ * off, otherwise a bitmask of the planes it is set in. Valid until the next call
+ * changing the instance.
+ */
+const uint8_t *chip8_screen(const Chip8 *chip8);
+
+/**
+ * Bytes of memory, 4096 or 65536 for xochip.
+ */
+size_t chip8_memory_size(const Chip8 *chip8);
+
+/**
+ * Copy len bytes of memory starting at address into out.
+ */
+Chip8Status chip8_read_memory(const Chip8 *chip8, size_t address, uint8_t *out, size_t len);
+
+/**
+ * Copy len bytes from data into memory starting at address.
+ */
+Chip8Status chip8_write_memory(Chip8 *chip8, size_t address, const uint8_t *data, size_t len);
+
+/**
+ * Read all registers at once.
+ */
+Chip8Status chip8_get_registers(const Chip8 *chip8, Chip8Registers *registers);
+
+/**
+ * Set all registers at once, pc and i have to point into memory.
+ */
+Chip8Status chip8_set_registers(Chip8 *chip8, const Chip8Registers *registers);
+
+/**
+ * Write a save state into out and return its size. Nothing gets written
+ * if out is null or len too small, so call it with null first to get the size.
+ */
+size_t chip8_save_state(const Chip8 *chip8, uint8_t *out, size_t len);
+
+/**
+ * Restore a save state of the same platform, nothing changes on errors.
+ */
+Chip8Status chip8_load_state(Chip8 *chip8, const uint8_t *data, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHIP8_H */
@@ -0,0 +1,440 @@
+// C api around the processor, to embed the emulator into C and C++ programs.
+// include/chip8.h is generated from this file with cbindgen, so the doc
+// comments here end up in the header.
+
+// the safety rules are the ones in include/chip8.h: valid pointers or null
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use chip8_rs::input::KeyEvent;
+use chip8_rs::platform::Platform;
+use chip8_rs::processor::Processor;
+use chip8_rs::{CYCLES_PER_FRAME, GAME_ENTRY};
+
+/// Width of the screen returned by chip8_screen, in pixels.
+pub const CHIP8_SCREEN_WIDTH: usize = 64;
+/// Height of the screen returned by chip8_screen, in pixels.
+pub const CHIP8_SCREEN_HEIGHT: usize = 32;
+
+/// An emulator instance, created by chip8_new and freed by chip8_free.
+pub struct Chip8 {
+    processor: Processor,
+    platform: Option<Platform>,
+    rom: Vec<u8>,
+    cycles_per_frame: usize,
+}
+
+/// Result of the calls, which can fail.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Status {
+    Ok = 0,
+    NullPointer,
+    InvalidArgument,
+    RomTooLarge,
+    OutOfBounds,
+    InvalidState,
+    /// The rom did something impossible, like returning with an empty stack or
+    /// reading past the end of memory. Reset or load a state to go on.
+    Crashed,
+}
+
+/// The cpu registers, as read by chip8_get_registers.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Chip8Registers {
+    pub v: [u8; 16],
+    pub i: u32,
+    pub pc: u32,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+/// A new instance with the default quirks and nothing loaded.
+#[no_mangle]
+pub extern "C" fn chip8_new() -> *mut Chip8 {
+    Box::into_raw(Box::new(Chip8 {
+        processor: Processor::new(),
+        platform: None,
+        rom: Vec::new(),
+        cycles_per_frame: CYCLES_PER_FRAME,
+    }))
+}
+
+/// Free an instance, null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+/// Emulate the quirks, memory and font of "chip8", "schip", "xochip" or "eti660".
+/// Call it before chip8_load_rom, as it clears the memory.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_platform(chip8: *mut Chip8, name: *const c_char) -> Chip8Status {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) if !name.is_null() => chip8,
+        _ => return Chip8Status::NullPointer,
+    };
+    let platform = match CStr::from_ptr(name)
+        .to_str()
+        .ok()
+        .and_then(Platform::from_name)
+    {
+        Some(platform) => platform,
+        None => return Chip8Status::InvalidArgument,
+    };
+    chip8.processor.set_platform(platform);
+    chip8.platform = Some(platform);
+    Chip8Status::Ok
+}
+
+/// Instructions run by chip8_run_frame, 10 by default.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_tickrate(
+    chip8: *mut Chip8,
+    cycles_per_frame: usize,
+) -> Chip8Status {
+    match chip8.as_mut() {
+        Some(_) if cycles_per_frame == 0 => Chip8Status::InvalidArgument,
+        Some(chip8) => {
+            chip8.cycles_per_frame = cycles_per_frame;
+            Chip8Status::Ok
+        }
+        None => Chip8Status::NullPointer,
+    }
+}
+
+/// Reset the processor and load a rom, the bytes get copied.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(
+    chip8: *mut Chip8,
+    data: *const u8,
+    len: usize,
+) -> Chip8Status {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) if !data.is_null() => chip8,
+        _ => return Chip8Status::NullPointer,
+    };
+    let load_address = chip8
+        .platform
+        .map(|platform| platform.load_address())
+        .unwrap_or(GAME_ENTRY);
+    if len > chip8.processor.memory().len() - load_address {
+        return Chip8Status::RomTooLarge;
+    }
+    chip8.rom = slice::from_raw_parts(data, len).to_vec();
+    chip8.processor.reset();
+    chip8.processor.load_game(&chip8.rom);
+    Chip8Status::Ok
+}
+
+/// Start the loaded rom over.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_reset(chip8: *mut Chip8) -> Chip8Status {
+    match chip8.as_mut() {
+        Some(chip8) => {
+            chip8.processor.reset();
+            chip8.processor.load_game(&chip8.rom);
+            Chip8Status::Ok
+        }
+        None => Chip8Status::NullPointer,
+    }
+}
+
+// run the processor without letting a panic cross into C
+unsafe fn run(
+    chip8: *mut Chip8,
+    redraw: *mut bool,
+    run: impl FnOnce(&mut Chip8) -> bool,
+) -> Chip8Status {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) => chip8,
+        None => return Chip8Status::NullPointer,
+    };
+    match panic::catch_unwind(AssertUnwindSafe(|| run(chip8))) {
+        Ok(changed) => {
+            if let Some(redraw) = redraw.as_mut() {
+                *redraw = changed;
+            }
+            Chip8Status::Ok
+        }
+        Err(_) => Chip8Status::Crashed,
+    }
+}
+
+/// Run a number of instructions without ticking the timers. redraw is set to
+/// whether the screen changed, it may be null.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(
+    chip8: *mut Chip8,
+    cycles: usize,
+    redraw: *mut bool,
+) -> Chip8Status {
+    run(chip8, redraw, |chip8| chip8.processor.run(cycles))
+}
+
+/// Run the instructions of a 60Hz frame and tick the timers. redraw is set to
+/// whether the screen changed, it may be null.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_run_frame(chip8: *mut Chip8, redraw: *mut bool) -> Chip8Status {
+    run(chip8, redraw, |chip8| {
+        chip8.processor.run_frame(chip8.cycles_per_frame)
+    })
+}
+
+/// Press or release a key of the hex keypad, 0 to 15.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(chip8: *mut Chip8, key: u8, pressed: bool) -> Chip8Status {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) => chip8,
+        None => return Chip8Status::NullPointer,
+    };
+    if key >= 16 {
+        return Chip8Status::InvalidArgument;
+    }
+    let key = key as usize;
+    chip8.processor.key_event(if pressed {
+        KeyEvent::Pressed(key)
+    } else {
+        KeyEvent::Released(key)
+    });
+    Chip8Status::Ok
+}
+
+/// CHIP8_SCREEN_WIDTH * CHIP8_SCREEN_HEIGHT pixels, row by row. A pixel is 0 when
+/// off, otherwise a bitmask of the planes it is set in. Valid until the next call
+/// changing the instance.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_screen(chip8: *const Chip8) -> *const u8 {
+    match chip8.as_ref() {
+        Some(chip8) => chip8.processor.screen().as_ptr() as *const u8,
+        None => ptr::null(),
+    }
+}
+
+/// Bytes of memory, 4096 or 65536 for xochip.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_memory_size(chip8: *const Chip8) -> usize {
+    match chip8.as_ref() {
+        Some(chip8) => chip8.processor.memory().len(),
+        None => 0,
+    }
+}
+
+/// Copy len bytes of memory starting at address into out.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_read_memory(
+    chip8: *const Chip8,
+    address: usize,
+    out: *mut u8,
+    len: usize,
+) -> Chip8Status {
+    let chip8 = match chip8.as_ref() {
+        Some(chip8) if !out.is_null() => chip8,
+        _ => return Chip8Status::NullPointer,
+    };
+    match chip8
+        .processor
+        .memory()
+        .get(address..address.saturating_add(len))
+    {
+        Some(memory) => {
+            ptr::copy_nonoverlapping(memory.as_ptr(), out, len);
+            Chip8Status::Ok
+        }
+        None => Chip8Status::OutOfBounds,
+    }
+}
+
+/// Copy len bytes from data into memory starting at address.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_write_memory(
+    chip8: *mut Chip8,
+    address: usize,
+    data: *const u8,
+    len: usize,
+) -> Chip8Status {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) if !data.is_null() => chip8,
+        _ => return Chip8Status::NullPointer,
+    };
+    if address.saturating_add(len) > chip8.processor.memory().len() {
+        return Chip8Status::OutOfBounds;
+    }
+    for (pos, &value) in slice::from_raw_parts(data, len).iter().enumerate() {
+        chip8.processor.write_memory(address + pos, value);
+    }
+    Chip8Status::Ok
+}
+
+/// Read all registers at once.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_registers(
+    chip8: *const Chip8,
+    registers: *mut Chip8Registers,
+) -> Chip8Status {
+    let (chip8, registers) = match (chip8.as_ref(), registers.as_mut()) {
+        (Some(chip8), Some(registers)) => (chip8, registers),
+        _ => return Chip8Status::NullPointer,
+    };
+    let processor = &chip8.processor;
+    let (delay_timer, sound_timer) = processor.timers();
+    *registers = Chip8Registers {
+        v: *processor.registers(),
+        i: processor.index() as u32,
+        pc: processor.pc() as u32,
+        delay_timer: delay_timer as u8,
+        sound_timer: sound_timer as u8,
+    };
+    Chip8Status::Ok
+}
+
+/// Set all registers at once, pc and i have to point into memory.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_registers(
+    chip8: *mut Chip8,
+    registers: *const Chip8Registers,
+) -> Chip8Status {
+    let (chip8, registers) = match (chip8.as_mut(), registers.as_ref()) {
+        (Some(chip8), Some(registers)) => (chip8, registers),
+        _ => return Chip8Status::NullPointer,
+    };
+    let processor = &mut chip8.processor;
+    let memory_size = processor.memory().len();
+    if registers.pc as usize + 1 >= memory_size || registers.i as usize >= memory_size {
+        return Chip8Status::OutOfBounds;
+    }
+    for (x, &value) in registers.v.iter().enumerate() {
+        processor.set_register(x, value);
+    }
+    processor.set_index(registers.i as usize);
+    processor.set_pc(registers.pc as usize);
+    processor.set_timers(
+        registers.delay_timer as usize,
+        registers.sound_timer as usize,
+    );
+    Chip8Status::Ok
+}
+
+/// Write a save state into out and return its size. Nothing gets written
+/// if out is null or len too small, so call it with null first to get the size.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_save_state(chip8: *const Chip8, out: *mut u8, len: usize) -> usize {
+    let state = match chip8.as_ref() {
+        Some(chip8) => chip8.processor.save_state(),
+        None => return 0,
+    };
+    if !out.is_null() && len >= state.len() {
+        ptr::copy_nonoverlapping(state.as_ptr(), out, state.len());
+    }
+    state.len()
+}
+
+/// Restore a save state of the same platform, nothing changes on errors.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_state(
+    chip8: *mut Chip8,
+    data: *const u8,
+    len: usize,
+) -> Chip8Status {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) if !data.is_null() => chip8,
+        _ => return Chip8Status::NullPointer,
+    };
+    match chip8.processor.load_state(slice::from_raw_parts(data, len)) {
+        Ok(()) => Chip8Status::Ok,
+        Err(_) => Chip8Status::InvalidState,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_screen_size() {
+        // literals, so cbindgen can put them into the header
+        assert_eq!(CHIP8_SCREEN_WIDTH, chip8_rs::SCREEN_WIDTH);
+        assert_eq!(CHIP8_SCREEN_HEIGHT, chip8_rs::SCREEN_HEIGHT);
+    }
+
+    #[test]
+    fn test_memory_bounds() {
+        unsafe {
+            let chip8 = chip8_new();
+            let mut buffer = [0; 4];
+            assert_eq!(
+                chip8_read_memory(chip8, 4094, buffer.as_mut_ptr(), 2),
+                Chip8Status::Ok
+            );
+            assert_eq!(
+                chip8_read_memory(chip8, 4094, buffer.as_mut_ptr(), 4),
+                Chip8Status::OutOfBounds
+            );
+            assert_eq!(
+                chip8_write_memory(chip8, usize::MAX, buffer.as_ptr(), 4),
+                Chip8Status::OutOfBounds
+            );
+            assert_eq!(
+                chip8_read_memory(ptr::null(), 0, buffer.as_mut_ptr(), 1),
+                Chip8Status::NullPointer
+            );
+            chip8_free(chip8);
+        }
+    }
+
+    #[test]
+    fn test_platform_and_rom_size() {
+        unsafe {
+            let chip8 = chip8_new();
+            let rom = vec![0; 4096];
+            assert_eq!(
+                chip8_load_rom(chip8, rom.as_ptr(), rom.len()),
+                Chip8Status::RomTooLarge
+            );
+            assert_eq!(
+                chip8_set_platform(chip8, b"xochip\0".as_ptr() as *const c_char),
+                Chip8Status::Ok
+            );
+            assert_eq!(chip8_memory_size(chip8), 65536);
+            assert_eq!(
+                chip8_load_rom(chip8, rom.as_ptr(), rom.len()),
+                Chip8Status::Ok
+            );
+            assert_eq!(
+                chip8_set_platform(chip8, b"nes\0".as_ptr() as *const c_char),
+                Chip8Status::InvalidArgument
+            );
+            chip8_free(chip8);
+        }
+    }
+
+    #[test]
+    fn test_crash() {
+        unsafe {
+            let chip8 = chip8_new();
+            // returns with an empty stack
+            let rom = [0x00, 0xee];
+            chip8_load_rom(chip8, rom.as_ptr(), rom.len());
+            let mut redraw = true;
+            assert_eq!(chip8_step(chip8, 1, &mut redraw), Chip8Status::Crashed);
+
+            let rom = [0x00, 0xe0];
+            chip8_load_rom(chip8, rom.as_ptr(), rom.len());
+            assert_eq!(chip8_step(chip8, 1, &mut redraw), Chip8Status::Ok);
+            assert!(redraw);
+            assert_eq!(
+                chip8_run_frame(ptr::null_mut(), ptr::null_mut()),
+                Chip8Status::NullPointer
+            );
+            chip8_free(chip8);
+        }
+    }
+}
@@ -0,0 +1,47 @@
+// Builds tests/test.c against the library and runs it, skipped without a C compiler
+#![cfg(unix)]
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_c_program() {
+    let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // cargo test builds the library into the deps directory of this test, the
+    // copy next to it may be left from an older cargo build
+    let exe = env::current_exe().unwrap();
+    let deps_dir = exe.parent().unwrap();
+    let program = env::temp_dir().join("chip8-capi-test");
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+
+    let status = Command::new(&compiler)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest.join("include"))
+        .arg(manifest.join("tests/test.c"))
+        .arg("-L")
+        .arg(deps_dir)
+        .arg(format!("-Wl,-rpath,{}", deps_dir.display()))
+        .arg("-lchip8")
+        .arg("-o")
+        .arg(&program)
+        .status();
+    match status {
+        Ok(status) => assert!(status.success(), "building the C test failed"),
+        Err(err) => {
+            eprintln!("skipped, no C compiler ({}): {}", compiler, err);
+            return;
+        }
+    }
+
+    let output = Command::new(&program).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}
@@ -0,0 +1,90 @@
+/* Runs a small rom through the C api, built and run by tests/c_api.rs */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "chip8.h"
+
+#define CHECK(condition)                                                \
+    if (!(condition)) {                                                 \
+        fprintf(stderr, "%s:%d: %s\n", __FILE__, __LINE__, #condition); \
+        return 1;                                                       \
+    }
+
+/* draws a 0 at (5, 0), then waits for a key into V3 and loops */
+static const uint8_t ROM[] = {
+    0x60, 0x05, 0x61, 0x00, 0xf1, 0x29, 0xd0, 0x15, 0xf3, 0x0a, 0x12, 0x08,
+};
+
+static int lit(const Chip8 *chip8) {
+    const uint8_t *screen = chip8_screen(chip8);
+    int count = 0;
+    for (int i = 0; i < CHIP8_SCREEN_WIDTH * CHIP8_SCREEN_HEIGHT; i++)
+        count += screen[i] != 0;
+    return count;
+}
+
+int main(void) {
+    Chip8 *chip8 = chip8_new();
+    CHECK(chip8);
+    CHECK(chip8_set_platform(chip8, "schip") == CHIP8_STATUS_OK);
+    CHECK(chip8_set_platform(chip8, "gameboy") == CHIP8_STATUS_INVALID_ARGUMENT);
+    CHECK(chip8_load_rom(chip8, ROM, sizeof ROM) == CHIP8_STATUS_OK);
+
+    bool redraw = false;
+    CHECK(chip8_step(chip8, 4, &redraw) == CHIP8_STATUS_OK);
+    CHECK(redraw);
+    CHECK(lit(chip8) == 14);
+
+    Chip8Registers registers;
+    CHECK(chip8_get_registers(chip8, &registers) == CHIP8_STATUS_OK);
+    CHECK(registers.v[0] == 5 && registers.pc == 0x208);
+
+    /* save while waiting for a key */
+    CHECK(chip8_step(chip8, 1, NULL) == CHIP8_STATUS_OK);
+    size_t size = chip8_save_state(chip8, NULL, 0);
+    CHECK(size > 0);
+    uint8_t *state = malloc(size);
+    CHECK(chip8_save_state(chip8, state, size) == size);
+
+    CHECK(chip8_set_key(chip8, 7, true) == CHIP8_STATUS_OK);
+    CHECK(chip8_set_key(chip8, 7, false) == CHIP8_STATUS_OK);
+    CHECK(chip8_set_key(chip8, 16, true) == CHIP8_STATUS_INVALID_ARGUMENT);
+    CHECK(chip8_run_frame(chip8, NULL) == CHIP8_STATUS_OK);
+    CHECK(chip8_get_registers(chip8, &registers) == CHIP8_STATUS_OK);
+    CHECK(registers.v[3] == 7);
+
+    /* poke a value and read it back */
+    uint8_t poke[] = {0xde, 0xad}, peek[2];
+    CHECK(chip8_write_memory(chip8, 0x300, poke, 2) == CHIP8_STATUS_OK);
+    CHECK(chip8_read_memory(chip8, 0x300, peek, 2) == CHIP8_STATUS_OK);
+    CHECK(memcmp(poke, peek, 2) == 0);
+    CHECK(chip8_read_memory(chip8, 4095, peek, 2) == CHIP8_STATUS_OUT_OF_BOUNDS);
+
+    registers.v[0xf] = 0x42;
+    CHECK(chip8_set_registers(chip8, &registers) == CHIP8_STATUS_OK);
+    registers.pc = 0x10000;
+    CHECK(chip8_set_registers(chip8, &registers) == CHIP8_STATUS_OUT_OF_BOUNDS);
+    registers.pc = 0x208;
+    registers.i = 0x10000;
+    CHECK(chip8_set_registers(chip8, &registers) == CHIP8_STATUS_OUT_OF_BOUNDS);
+
+    CHECK(chip8_load_state(chip8, state, size) == CHIP8_STATUS_OK);
+    CHECK(chip8_get_registers(chip8, &registers) == CHIP8_STATUS_OK);
+    CHECK(registers.v[3] == 0 && registers.v[0xf] != 0x42);
+    CHECK(chip8_load_state(chip8, state, size / 2) == CHIP8_STATUS_INVALID_STATE);
+
+    CHECK(chip8_reset(chip8) == CHIP8_STATUS_OK);
+    CHECK(lit(chip8) == 0);
+
+    /* a return with an empty stack doesn't take the program down */
+    static const uint8_t CRASH[] = {0x00, 0xee};
+    CHECK(chip8_load_rom(chip8, CRASH, sizeof CRASH) == CHIP8_STATUS_OK);
+    CHECK(chip8_step(chip8, 1, NULL) == CHIP8_STATUS_CRASHED);
+
+    free(state);
+    chip8_free(chip8);
+    puts("ok");
+    return 0;
+}
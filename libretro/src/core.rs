@@ -0,0 +1,333 @@
+use std::collections::BTreeMap;
+
+use chip8_rs::beeper::Beeper;
+use chip8_rs::cartridge::{Cartridge, CartridgeError};
+use chip8_rs::cheat::{Cheat, CheatList};
+use chip8_rs::database::{Database, RomInfo};
+use chip8_rs::fontset::{Font, BIG_FONT};
+use chip8_rs::input::{Controls, KeyEvent};
+use chip8_rs::palette::Palette;
+use chip8_rs::platform::Platform;
+use chip8_rs::processor::Processor;
+use chip8_rs::state::StateError;
+use chip8_rs::{
+    CYCLES_PER_FRAME, FRAME_RATE, GAME_ENTRY, MEMORY_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH,
+};
+
+use crate::ffi;
+
+pub const SAMPLE_RATE: u32 = 44100;
+pub const SAMPLES_PER_FRAME: usize = (SAMPLE_RATE / FRAME_RATE) as usize;
+
+// What the user picked in the core options, None leaves it to the rom
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Settings {
+    pub platform: Option<Platform>,
+    pub tickrate: Option<usize>,
+    pub palette: Option<Palette>,
+}
+
+// A loaded game and everything needed to run it a frame at a time
+pub struct Core {
+    processor: Processor,
+    cartridge: Cartridge,
+    info: Option<RomInfo>,
+    settings: Settings,
+    cycles_per_frame: usize,
+    palette: Palette,
+    controls: Controls,
+    keys: [bool; 16], // keypad state of the last frame
+    beeper: Beeper,
+    video: Vec<u32>,
+    audio: Vec<i16>,                  // interleaved stereo
+    codes: BTreeMap<u32, Vec<Cheat>>, // enabled cheats by the frontend's index
+    cheats: CheatList,
+}
+
+impl Core {
+    pub fn new(mut cartridge: Cartridge, settings: Settings) -> Result<Self, CartridgeError> {
+        // settings coming with the cartridge win, otherwise look the rom up
+        let info = match cartridge.info.take() {
+            Some(info) => Some(info),
+            None => Database::bundled().lookup(&cartridge.sha1()),
+        };
+        let mut core = Core {
+            processor: Processor::new(),
+            cartridge,
+            info,
+            settings,
+            cycles_per_frame: CYCLES_PER_FRAME,
+            palette: Palette::default(),
+            controls: Controls::default(),
+            keys: [false; 16],
+            beeper: Beeper::new(SAMPLE_RATE),
+            video: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            audio: vec![0; SAMPLES_PER_FRAME * 2],
+            codes: BTreeMap::new(),
+            cheats: CheatList::new(),
+        };
+        core.configure()?;
+        Ok(core)
+    }
+
+    // same precedence as the command line, the core options win over the rom
+    fn configure(&mut self) -> Result<(), CartridgeError> {
+        let info = self.info.as_ref();
+        let platform = self.settings.platform;
+        let quirks = match platform {
+            Some(platform) => platform.quirks(),
+            None => info.and_then(|info| info.quirks).unwrap_or_default(),
+        };
+        let platform = platform.or_else(|| info.and_then(|info| info.platform));
+        let memory_size = platform
+            .map(|platform| platform.memory_size())
+            .unwrap_or(MEMORY_SIZE);
+        let load_address = platform
+            .map(|platform| platform.load_address())
+            .unwrap_or(GAME_ENTRY);
+        let font = platform
+            .map(|platform| platform.font())
+            .unwrap_or(Font::Classic);
+        self.cartridge.relocate(load_address)?;
+        self.cartridge.validate(memory_size - load_address)?;
+
+        self.processor = Processor::new();
+        self.processor.set_quirks(quirks);
+        self.processor.set_memory_size(memory_size);
+        self.processor.set_load_address(load_address);
+        self.processor.set_font(font.data(), &BIG_FONT, 0);
+        self.processor.load_game(self.cartridge.data());
+        self.keys = [false; 16];
+        self.controls = info.map(|info| info.controls).unwrap_or_default();
+        self.apply_speed_and_colors();
+        Ok(())
+    }
+
+    fn apply_speed_and_colors(&mut self) {
+        let info = self.info.as_ref();
+        self.cycles_per_frame = self
+            .settings
+            .tickrate
+            .or_else(|| info.and_then(|info| info.tickrate))
+            .unwrap_or(CYCLES_PER_FRAME);
+        self.palette = match (self.settings.palette, info) {
+            (Some(palette), _) => palette,
+            (None, Some(info)) => {
+                let mut palette = Palette::default();
+                for (pos, &color) in info.colors.iter().take(4).enumerate() {
+                    palette.colors[pos] = color;
+                }
+                palette
+            }
+            (None, None) => Palette::default(),
+        };
+    }
+
+    // a different platform restarts the game, as memory and quirks change
+    pub fn set_settings(&mut self, settings: Settings) {
+        let restart = settings.platform != self.settings.platform;
+        let previous = self.settings;
+        self.settings = settings;
+        if restart {
+            // keep the old platform, if the rom doesn't fit into the new one
+            if self.configure().is_err() {
+                self.settings.platform = previous.platform;
+                let _ = self.configure();
+            }
+        } else {
+            self.apply_speed_and_colors();
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.processor.reset();
+        self.processor.load_game(self.cartridge.data());
+        self.keys = [false; 16];
+    }
+
+    // emulate a frame with the retropad buttons held down, indexed by RETRO_DEVICE_ID_JOYPAD_*
+    pub fn run_frame(&mut self, buttons: &[bool; ffi::JOYPAD_BUTTONS]) {
+        let mut keys = [false; 16];
+        for (button, _) in buttons.iter().enumerate().filter(|(_, &held)| held) {
+            if let Some(key) = keymap(button, &self.controls) {
+                keys[key] = true;
+            }
+        }
+        for (key, (&held, &was_held)) in keys.iter().zip(self.keys.iter()).enumerate() {
+            match (held, was_held) {
+                (true, false) => self.processor.key_event(KeyEvent::Pressed(key)),
+                (false, true) => self.processor.key_event(KeyEvent::Released(key)),
+                _ => {}
+            }
+        }
+        self.keys = keys;
+
+        self.processor.run(self.cycles_per_frame);
+        let (_, sound_timer) = self.processor.timers();
+        self.processor.tick_timers();
+        self.cheats.apply(&mut self.processor);
+        self.beeper.fill(sound_timer > 0, &mut self.audio);
+
+        let pixels = self.processor.screen().iter().flatten();
+        for (color, &pixel) in self.video.iter_mut().zip(pixels) {
+            *color = self.palette.color(pixel);
+        }
+    }
+
+    // no cheats removes the ones at index, later indices win on the same address
+    pub fn set_cheat(&mut self, index: u32, cheats: Vec<Cheat>) {
+        if cheats.is_empty() {
+            self.codes.remove(&index);
+        } else {
+            self.codes.insert(index, cheats);
+        }
+        self.cheats.clear();
+        for &cheat in self.codes.values().flatten() {
+            self.cheats.add(cheat, "");
+        }
+    }
+
+    pub fn reset_cheats(&mut self) {
+        self.codes.clear();
+        self.cheats.clear();
+    }
+
+    // XRGB8888 pixels of the last frame
+    pub fn video(&self) -> &[u32] {
+        &self.video
+    }
+
+    // samples of the last frame
+    pub fn audio(&self) -> &[i16] {
+        &self.audio
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        self.processor.save_state()
+    }
+
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        self.processor.load_state(state)?;
+        // buttons held now get pressed again in the next frame
+        self.keys = [false; 16];
+        Ok(())
+    }
+}
+
+// keypad key of a retropad button, the d-pad and a/b follow the
+// controls of the rom, every other key gets a button of its own
+pub fn keymap(button: usize, controls: &Controls) -> Option<usize> {
+    let key = match button {
+        ffi::JOYPAD_UP => controls.up.unwrap_or(0x2),
+        ffi::JOYPAD_DOWN => controls.down.unwrap_or(0x8),
+        ffi::JOYPAD_LEFT => controls.left.unwrap_or(0x4),
+        ffi::JOYPAD_RIGHT => controls.right.unwrap_or(0x6),
+        ffi::JOYPAD_A => controls.a.unwrap_or(0x5),
+        ffi::JOYPAD_B => controls.b.unwrap_or(0x0),
+        ffi::JOYPAD_X => 0xa,
+        ffi::JOYPAD_Y => 0xb,
+        ffi::JOYPAD_L => 0xc,
+        ffi::JOYPAD_R => 0xd,
+        ffi::JOYPAD_SELECT => 0xe,
+        ffi::JOYPAD_START => 0xf,
+        ffi::JOYPAD_L2 => 0x1,
+        ffi::JOYPAD_R2 => 0x3,
+        ffi::JOYPAD_L3 => 0x7,
+        ffi::JOYPAD_R3 => 0x9,
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // draws a 0 at (5, 0) and beeps for 10 frames
+    const ROM: [u8; 14] = [
+        0x60, 0x05, 0x61, 0x00, 0xf1, 0x29, 0xd0, 0x15, 0x62, 0x0a, 0xf2, 0x18, 0x12, 0x0c,
+    ];
+
+    fn new_core(settings: Settings) -> Core {
+        let cartridge = Cartridge::from_data(ROM.to_vec(), "test.ch8", None).unwrap();
+        Core::new(cartridge, settings).unwrap()
+    }
+
+    #[test]
+    fn test_keymap_covers_keypad() {
+        let controls = Controls::default();
+        let mut keys: Vec<usize> = (0..ffi::JOYPAD_BUTTONS)
+            .filter_map(|button| keymap(button, &controls))
+            .collect();
+        keys.sort_unstable();
+        assert_eq!(keys, (0..16).collect::<Vec<_>>());
+
+        let controls = Controls {
+            a: Some(0x6),
+            ..Controls::default()
+        };
+        assert_eq!(keymap(ffi::JOYPAD_A, &controls), Some(0x6));
+    }
+
+    #[test]
+    fn test_run_frame() {
+        let mut core = new_core(Settings::default());
+        core.run_frame(&[false; ffi::JOYPAD_BUTTONS]);
+
+        let lit = core.video().iter().filter(|&&color| color != 0).count();
+        assert_eq!(lit, 14);
+        assert_eq!(core.video()[5], 0xffffff);
+        assert!(core.audio().iter().any(|&sample| sample != 0));
+
+        // the palette changes without a restart
+        core.set_settings(Settings {
+            palette: Palette::from_name("amber"),
+            ..Settings::default()
+        });
+        core.run_frame(&[false; ffi::JOYPAD_BUTTONS]);
+        assert_eq!(core.video()[5], 0xffb000);
+    }
+
+    #[test]
+    fn test_cheats() {
+        let mut core = new_core(Settings::default());
+        core.set_cheat(
+            0,
+            vec![Cheat {
+                address: 0x300,
+                value: 1,
+            }],
+        );
+        core.set_cheat(
+            1,
+            vec![Cheat {
+                address: 0x300,
+                value: 2,
+            }],
+        );
+        core.run_frame(&[false; ffi::JOYPAD_BUTTONS]);
+        assert_eq!(core.processor.memory()[0x300], 2);
+
+        core.set_cheat(1, Vec::new());
+        core.run_frame(&[false; ffi::JOYPAD_BUTTONS]);
+        assert_eq!(core.processor.memory()[0x300], 1);
+    }
+
+    #[test]
+    fn test_buttons_press_keys() {
+        // waits for a key and stores it in V0
+        let rom = vec![0xf0, 0x0a, 0x12, 0x02];
+        let cartridge = Cartridge::from_data(rom, "test.ch8", None).unwrap();
+        let mut core = Core::new(cartridge, Settings::default()).unwrap();
+
+        let mut buttons = [false; ffi::JOYPAD_BUTTONS];
+        core.run_frame(&buttons);
+        buttons[ffi::JOYPAD_START] = true;
+        core.run_frame(&buttons);
+        assert_eq!(core.processor.registers()[0], 0);
+
+        // fx0a continues on release
+        core.run_frame(&[false; ffi::JOYPAD_BUTTONS]);
+        assert_eq!(core.processor.registers()[0], 0xf);
+    }
+}
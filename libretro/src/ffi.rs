@@ -0,0 +1,86 @@
+// The parts of libretro.h this core needs
+
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+
+pub const API_VERSION: c_uint = 1;
+
+pub const ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+pub const ENVIRONMENT_GET_VARIABLE: c_uint = 15;
+pub const ENVIRONMENT_SET_VARIABLES: c_uint = 16;
+pub const ENVIRONMENT_GET_VARIABLE_UPDATE: c_uint = 17;
+
+pub const PIXEL_FORMAT_XRGB8888: c_int = 1;
+pub const DEVICE_JOYPAD: c_uint = 1;
+pub const REGION_NTSC: c_uint = 0;
+
+// RETRO_DEVICE_ID_JOYPAD_*
+pub const JOYPAD_BUTTONS: usize = 16;
+pub const JOYPAD_B: usize = 0;
+pub const JOYPAD_Y: usize = 1;
+pub const JOYPAD_SELECT: usize = 2;
+pub const JOYPAD_START: usize = 3;
+pub const JOYPAD_UP: usize = 4;
+pub const JOYPAD_DOWN: usize = 5;
+pub const JOYPAD_LEFT: usize = 6;
+pub const JOYPAD_RIGHT: usize = 7;
+pub const JOYPAD_A: usize = 8;
+pub const JOYPAD_X: usize = 9;
+pub const JOYPAD_L: usize = 10;
+pub const JOYPAD_R: usize = 11;
+pub const JOYPAD_L2: usize = 12;
+pub const JOYPAD_R2: usize = 13;
+pub const JOYPAD_L3: usize = 14;
+pub const JOYPAD_R3: usize = 15;
+
+pub type EnvironmentFn = unsafe extern "C" fn(cmd: c_uint, data: *mut c_void) -> bool;
+pub type VideoRefreshFn =
+    unsafe extern "C" fn(data: *const c_void, width: c_uint, height: c_uint, pitch: usize);
+pub type AudioSampleFn = unsafe extern "C" fn(left: i16, right: i16);
+pub type AudioSampleBatchFn = unsafe extern "C" fn(data: *const i16, frames: usize) -> usize;
+pub type InputPollFn = unsafe extern "C" fn();
+pub type InputStateFn =
+    unsafe extern "C" fn(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16;
+
+#[repr(C)]
+pub struct SystemInfo {
+    pub library_name: *const c_char,
+    pub library_version: *const c_char,
+    pub valid_extensions: *const c_char,
+    pub need_fullpath: bool,
+    pub block_extract: bool,
+}
+
+#[repr(C)]
+pub struct GameGeometry {
+    pub base_width: c_uint,
+    pub base_height: c_uint,
+    pub max_width: c_uint,
+    pub max_height: c_uint,
+    pub aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct SystemTiming {
+    pub fps: f64,
+    pub sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct SystemAvInfo {
+    pub geometry: GameGeometry,
+    pub timing: SystemTiming,
+}
+
+#[repr(C)]
+pub struct GameInfo {
+    pub path: *const c_char,
+    pub data: *const c_void,
+    pub size: usize,
+    pub meta: *const c_char,
+}
+
+#[repr(C)]
+pub struct Variable {
+    pub key: *const c_char,
+    pub value: *const c_char,
+}
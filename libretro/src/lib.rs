@@ -0,0 +1,374 @@
+// libretro core, to run chip8-rs inside RetroArch and other libretro frontends.
+// The frontend drives everything through the retro_* functions below, the
+// core keeps its state in globals, as the api has no handle to pass around.
+
+// the safety rules of all exported functions are the ones of libretro.h
+#![allow(clippy::missing_safety_doc)]
+
+mod core;
+mod ffi;
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_uint, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use chip8_rs::cartridge::Cartridge;
+use chip8_rs::cheat::Cheat;
+use chip8_rs::palette::Palette;
+use chip8_rs::platform::Platform;
+use chip8_rs::{FRAME_RATE, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use crate::core::{Core, Settings, SAMPLES_PER_FRAME, SAMPLE_RATE};
+
+// key and "description; default|other values" of each core option
+const VARIABLES: [(&[u8], &[u8]); 3] = [
+    (
+        b"chip8_platform\0",
+        b"Platform (restarts the game); auto|chip8|schip|xochip|eti660\0",
+    ),
+    (
+        b"chip8_tickrate\0",
+        b"Instructions per frame; auto|10|15|20|30|50|100|200|500|1000\0",
+    ),
+    (
+        b"chip8_palette\0",
+        b"Palette; auto|classic|phosphor|amber|lcd|octo|contrast\0",
+    ),
+];
+
+#[derive(Clone, Copy, Default)]
+struct Callbacks {
+    environment: Option<ffi::EnvironmentFn>,
+    video_refresh: Option<ffi::VideoRefreshFn>,
+    audio_sample_batch: Option<ffi::AudioSampleBatchFn>,
+    input_poll: Option<ffi::InputPollFn>,
+    input_state: Option<ffi::InputStateFn>,
+}
+
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+fn callbacks() -> Callbacks {
+    *CALLBACKS.lock().unwrap()
+}
+
+// never panics, even if a panic poisoned the lock before
+fn lock_core() -> MutexGuard<'static, Option<Core>> {
+    CORE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// run on the loaded game without letting a panic cross into the frontend,
+// a crashed game is unloaded and everything after it does nothing
+fn with_core<T>(fallback: T, run: impl FnOnce(&mut Core) -> T) -> T {
+    let mut core = lock_core();
+    let result = match core.as_mut() {
+        Some(loaded) => panic::catch_unwind(AssertUnwindSafe(|| run(loaded))),
+        None => return fallback,
+    };
+    result.unwrap_or_else(|_| {
+        *core = None;
+        fallback
+    })
+}
+
+unsafe fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    match callbacks().environment {
+        Some(environment) => environment(cmd, data),
+        None => false,
+    }
+}
+
+// value of a core option, None if the frontend doesn't know it
+unsafe fn variable(key: &[u8]) -> Option<String> {
+    let mut variable = ffi::Variable {
+        key: key.as_ptr() as *const c_char,
+        value: ptr::null(),
+    };
+    let found = environment(
+        ffi::ENVIRONMENT_GET_VARIABLE,
+        &mut variable as *mut ffi::Variable as *mut c_void,
+    );
+    if !found || variable.value.is_null() {
+        return None;
+    }
+    Some(
+        CStr::from_ptr(variable.value)
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+// "auto" and unknown values leave the setting to the rom
+unsafe fn settings() -> Settings {
+    Settings {
+        platform: variable(VARIABLES[0].0).and_then(|name| Platform::from_name(&name)),
+        tickrate: variable(VARIABLES[1].0).and_then(|tickrate| tickrate.parse().ok()),
+        palette: variable(VARIABLES[2].0).and_then(|name| Palette::from_name(&name)),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    ffi::API_VERSION
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_environment(callback: Option<ffi::EnvironmentFn>) {
+    CALLBACKS.lock().unwrap().environment = callback;
+
+    let mut variables: Vec<ffi::Variable> = VARIABLES
+        .iter()
+        .map(|(key, value)| ffi::Variable {
+            key: key.as_ptr() as *const c_char,
+            value: value.as_ptr() as *const c_char,
+        })
+        .collect();
+    variables.push(ffi::Variable {
+        key: ptr::null(),
+        value: ptr::null(),
+    });
+    environment(
+        ffi::ENVIRONMENT_SET_VARIABLES,
+        variables.as_mut_ptr() as *mut c_void,
+    );
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: Option<ffi::VideoRefreshFn>) {
+    CALLBACKS.lock().unwrap().video_refresh = callback;
+}
+
+// all samples of a frame go out in a single batch
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_callback: Option<ffi::AudioSampleFn>) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: Option<ffi::AudioSampleBatchFn>) {
+    CALLBACKS.lock().unwrap().audio_sample_batch = callback;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: Option<ffi::InputPollFn>) {
+    CALLBACKS.lock().unwrap().input_poll = callback;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: Option<ffi::InputStateFn>) {
+    CALLBACKS.lock().unwrap().input_state = callback;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    *lock_core() = None;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut ffi::SystemInfo) {
+    *info = ffi::SystemInfo {
+        library_name: b"chip8-rs\0".as_ptr() as *const c_char,
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+        valid_extensions: b"ch8|c8|sc8|xo8|gif|zip\0".as_ptr() as *const c_char,
+        need_fullpath: false,
+        block_extract: false, // zip archives get opened like on the command line
+    };
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut ffi::SystemAvInfo) {
+    *info = ffi::SystemAvInfo {
+        geometry: ffi::GameGeometry {
+            base_width: SCREEN_WIDTH as c_uint,
+            base_height: SCREEN_HEIGHT as c_uint,
+            max_width: SCREEN_WIDTH as c_uint,
+            max_height: SCREEN_HEIGHT as c_uint,
+            aspect_ratio: SCREEN_WIDTH as f32 / SCREEN_HEIGHT as f32,
+        },
+        timing: ffi::SystemTiming {
+            fps: FRAME_RATE as f64,
+            sample_rate: SAMPLE_RATE as f64,
+        },
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    with_core((), Core::reset);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_run() {
+    with_core((), |core| run_frame(core));
+}
+
+unsafe fn run_frame(core: &mut Core) {
+    let callbacks = callbacks();
+    let mut updated = false;
+    environment(
+        ffi::ENVIRONMENT_GET_VARIABLE_UPDATE,
+        &mut updated as *mut bool as *mut c_void,
+    );
+    if updated {
+        core.set_settings(settings());
+    }
+
+    let mut buttons = [false; ffi::JOYPAD_BUTTONS];
+    if let (Some(input_poll), Some(input_state)) = (callbacks.input_poll, callbacks.input_state) {
+        input_poll();
+        for (button, held) in buttons.iter_mut().enumerate() {
+            *held = input_state(0, ffi::DEVICE_JOYPAD, 0, button as c_uint) != 0;
+        }
+    }
+
+    core.run_frame(&buttons);
+
+    if let Some(video_refresh) = callbacks.video_refresh {
+        let video = core.video();
+        video_refresh(
+            video.as_ptr() as *const c_void,
+            SCREEN_WIDTH as c_uint,
+            SCREEN_HEIGHT as c_uint,
+            SCREEN_WIDTH * 4,
+        );
+    }
+    if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+        audio_sample_batch(core.audio().as_ptr(), SAMPLES_PER_FRAME);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    with_core(0, |core| core.save_state().len())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let state = match with_core(None, |core| Some(core.save_state())) {
+        Some(state) => state,
+        None => return false,
+    };
+    if data.is_null() || size < state.len() {
+        return false;
+    }
+    ptr::copy_nonoverlapping(state.as_ptr(), data as *mut u8, state.len());
+    true
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    if data.is_null() {
+        return false;
+    }
+    let state = slice::from_raw_parts(data as *const u8, size);
+    with_core(false, |core| core.load_state(state).is_ok())
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {
+    with_core((), Core::reset_cheats);
+}
+
+// codes like 2f0:03 of the cheat lists, several joined with +, invalid ones are left out
+#[no_mangle]
+pub unsafe extern "C" fn retro_cheat_set(index: c_uint, enabled: bool, code: *const c_char) {
+    let cheats = if enabled && !code.is_null() {
+        let code = CStr::from_ptr(code).to_string_lossy();
+        code.split('+').filter_map(Cheat::parse).collect()
+    } else {
+        Vec::new()
+    };
+    with_core((), |core| core.set_cheat(index, cheats));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const ffi::GameInfo) -> bool {
+    if game.is_null() || (*game).data.is_null() {
+        return false;
+    }
+    let game = &*game;
+
+    let mut format = ffi::PIXEL_FORMAT_XRGB8888;
+    if !environment(
+        ffi::ENVIRONMENT_SET_PIXEL_FORMAT,
+        &mut format as *mut _ as *mut c_void,
+    ) {
+        return false;
+    }
+
+    let data = slice::from_raw_parts(game.data as *const u8, game.size).to_vec();
+    let name = if game.path.is_null() {
+        "game".to_string()
+    } else {
+        CStr::from_ptr(game.path).to_string_lossy().into_owned()
+    };
+    let settings = settings();
+    let core = panic::catch_unwind(|| {
+        Cartridge::from_data(data, &name, None).and_then(|cartridge| Core::new(cartridge, settings))
+    });
+    match core {
+        Ok(Ok(core)) => {
+            *lock_core() = Some(core);
+            true
+        }
+        _ => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const ffi::GameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    *lock_core() = None;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    ffi::REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(_id: c_uint) -> *mut c_void {
+    ptr::null_mut()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(_id: c_uint) -> usize {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crashed_core_is_unloaded() {
+        let cartridge = Cartridge::from_data(vec![0x12, 0x00], "loop.ch8", None).unwrap();
+        *lock_core() = Some(Core::new(cartridge, Settings::default()).unwrap());
+
+        assert!(with_core(false, |_| true));
+        assert!(!with_core(false, |_| panic!("bad rom")));
+        assert!(lock_core().is_none());
+        assert_eq!(retro_serialize_size(), 0);
+    }
+}
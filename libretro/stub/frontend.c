@@ -0,0 +1,219 @@
+/*
+ * Minimal libretro frontend to test the core with: loads it with dlopen,
+ * runs a small rom for a few frames and checks video, audio and save states.
+ *
+ *   cc -o frontend frontend.c -ldl && ./frontend libchip8_libretro.so
+ */
+
+#include <dlfcn.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+/* the parts of libretro.h used here */
+#define RETRO_ENVIRONMENT_SET_PIXEL_FORMAT 10
+#define RETRO_ENVIRONMENT_GET_VARIABLE 15
+#define RETRO_ENVIRONMENT_SET_VARIABLES 16
+#define RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE 17
+#define RETRO_PIXEL_FORMAT_XRGB8888 1
+#define RETRO_DEVICE_ID_JOYPAD_START 3
+
+struct retro_system_info {
+    const char *library_name;
+    const char *library_version;
+    const char *valid_extensions;
+    bool need_fullpath;
+    bool block_extract;
+};
+
+struct retro_game_geometry {
+    unsigned base_width, base_height, max_width, max_height;
+    float aspect_ratio;
+};
+
+struct retro_system_timing {
+    double fps, sample_rate;
+};
+
+struct retro_system_av_info {
+    struct retro_game_geometry geometry;
+    struct retro_system_timing timing;
+};
+
+struct retro_game_info {
+    const char *path;
+    const void *data;
+    size_t size;
+    const char *meta;
+};
+
+struct retro_variable {
+    const char *key;
+    const char *value;
+};
+
+typedef bool (*environment_t)(unsigned, void *);
+typedef void (*video_refresh_t)(const void *, unsigned, unsigned, size_t);
+typedef size_t (*audio_sample_batch_t)(const int16_t *, size_t);
+typedef void (*input_poll_t)(void);
+typedef int16_t (*input_state_t)(unsigned, unsigned, unsigned, unsigned);
+
+/* draws a 0 at (5, 0), beeps for 10 frames and waits for a key into V3 */
+static const uint8_t ROM[] = {
+    0x60, 0x05, 0x61, 0x00, 0xf1, 0x29, 0xd0, 0x15,
+    0x62, 0x0a, 0xf2, 0x18, 0xf3, 0x0a, 0x12, 0x0e,
+};
+
+static int pixel_format = -1;
+static int variables = 0;
+static int frames = 0;
+static int lit = 0;
+static size_t samples = 0;
+static size_t loud = 0;
+static bool start = false;
+
+static bool environment(unsigned cmd, void *data) {
+    switch (cmd) {
+    case RETRO_ENVIRONMENT_SET_PIXEL_FORMAT:
+        pixel_format = *(const int *)data;
+        return true;
+    case RETRO_ENVIRONMENT_SET_VARIABLES:
+        for (const struct retro_variable *var = data; var->key; var++)
+            variables++;
+        return true;
+    case RETRO_ENVIRONMENT_GET_VARIABLE: {
+        struct retro_variable *var = data;
+        if (strcmp(var->key, "chip8_tickrate") == 0) {
+            var->value = "20";
+            return true;
+        }
+        return false;
+    }
+    case RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE:
+        *(bool *)data = false;
+        return true;
+    default:
+        return false;
+    }
+}
+
+static void video_refresh(const void *data, unsigned width, unsigned height, size_t pitch) {
+    frames++;
+    lit = 0;
+    for (unsigned y = 0; y < height; y++) {
+        const uint32_t *row = (const uint32_t *)((const uint8_t *)data + y * pitch);
+        for (unsigned x = 0; x < width; x++)
+            lit += (row[x] & 0xffffff) != 0;
+    }
+}
+
+static size_t audio_sample_batch(const int16_t *data, size_t count) {
+    for (size_t i = 0; i < count * 2; i++)
+        loud += data[i] != 0;
+    samples += count;
+    return count;
+}
+
+static void input_poll(void) {}
+
+static int16_t input_state(unsigned port, unsigned device, unsigned index, unsigned id) {
+    (void)port, (void)device, (void)index;
+    return start && id == RETRO_DEVICE_ID_JOYPAD_START;
+}
+
+#define CHECK(condition)                                          \
+    if (!(condition)) {                                           \
+        fprintf(stderr, "%s:%d: %s\n", __FILE__, __LINE__, #condition); \
+        return 1;                                                 \
+    }
+
+#define LOAD(name) void *name = dlsym(core, #name); CHECK(name)
+
+int main(int argc, char **argv) {
+    CHECK(argc == 2);
+    void *core = dlopen(argv[1], RTLD_NOW);
+    if (!core) {
+        fprintf(stderr, "%s\n", dlerror());
+        return 1;
+    }
+
+    LOAD(retro_api_version);
+    LOAD(retro_set_environment);
+    LOAD(retro_set_video_refresh);
+    LOAD(retro_set_audio_sample_batch);
+    LOAD(retro_set_input_poll);
+    LOAD(retro_set_input_state);
+    LOAD(retro_init);
+    LOAD(retro_deinit);
+    LOAD(retro_get_system_info);
+    LOAD(retro_get_system_av_info);
+    LOAD(retro_load_game);
+    LOAD(retro_unload_game);
+    LOAD(retro_run);
+    LOAD(retro_serialize_size);
+    LOAD(retro_serialize);
+    LOAD(retro_unserialize);
+
+    CHECK(((unsigned (*)(void))retro_api_version)() == 1);
+    ((void (*)(environment_t))retro_set_environment)(environment);
+    ((void (*)(video_refresh_t))retro_set_video_refresh)(video_refresh);
+    ((void (*)(audio_sample_batch_t))retro_set_audio_sample_batch)(audio_sample_batch);
+    ((void (*)(input_poll_t))retro_set_input_poll)(input_poll);
+    ((void (*)(input_state_t))retro_set_input_state)(input_state);
+    ((void (*)(void))retro_init)();
+    CHECK(variables == 3);
+
+    struct retro_system_info info;
+    ((void (*)(struct retro_system_info *))retro_get_system_info)(&info);
+    CHECK(strcmp(info.library_name, "chip8-rs") == 0);
+
+    struct retro_game_info game = {"test.ch8", ROM, sizeof ROM, NULL};
+    CHECK(((bool (*)(const struct retro_game_info *))retro_load_game)(&game));
+    CHECK(pixel_format == RETRO_PIXEL_FORMAT_XRGB8888);
+
+    struct retro_system_av_info av;
+    ((void (*)(struct retro_system_av_info *))retro_get_system_av_info)(&av);
+    CHECK(av.geometry.base_width == 64 && av.geometry.base_height == 32);
+
+    void (*run)(void) = (void (*)(void))retro_run;
+    for (int i = 0; i < 10; i++)
+        run();
+    CHECK(frames == 10);
+    CHECK(lit == 14);
+    CHECK(samples == 10 * av.timing.sample_rate / av.timing.fps);
+    CHECK(loud > 0);
+
+    /* the beep is over */
+    loud = 0;
+    run();
+    CHECK(loud == 0);
+
+    size_t size = ((size_t (*)(void))retro_serialize_size)();
+    CHECK(size > 0);
+    uint8_t *state = malloc(size);
+    uint8_t *again = malloc(size);
+    CHECK(((bool (*)(void *, size_t))retro_serialize)(state, size));
+
+    /* press and release start, then go back to before */
+    start = true;
+    run();
+    start = false;
+    run();
+    CHECK(((bool (*)(void *, size_t))retro_serialize)(again, size));
+    CHECK(memcmp(state, again, size) != 0);
+    CHECK(((bool (*)(const void *, size_t))retro_unserialize)(state, size));
+    CHECK(((bool (*)(void *, size_t))retro_serialize)(again, size));
+    CHECK(memcmp(state, again, size) == 0);
+    CHECK(!((bool (*)(const void *, size_t))retro_unserialize)(state, size / 2));
+
+    free(state);
+    free(again);
+    ((void (*)(void))retro_unload_game)();
+    ((void (*)(void))retro_deinit)();
+    dlclose(core);
+    puts("ok");
+    return 0;
+}
@@ -0,0 +1,51 @@
+// Builds the C stub frontend and runs the core with it, skipped without a C compiler
+#![cfg(unix)]
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+// the core lands next to the deps directory of this test
+fn core_path() -> PathBuf {
+    let exe = env::current_exe().unwrap();
+    let dir = exe.parent().unwrap().parent().unwrap();
+    let name = if cfg!(target_os = "macos") {
+        "libchip8_libretro.dylib"
+    } else {
+        "libchip8_libretro.so"
+    };
+    // cargo test builds the cdylib into deps, the one next to it may be left from an older build
+    [dir.join("deps").join(name), dir.join(name)]
+        .iter()
+        .find(|path| path.exists())
+        .cloned()
+        .unwrap_or_else(|| dir.join("deps").join(name))
+}
+
+#[test]
+fn test_stub_frontend() {
+    let source = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("stub/frontend.c");
+    let frontend = env::temp_dir().join("chip8-libretro-frontend");
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+
+    let mut build = Command::new(&compiler);
+    build.arg("-o").arg(&frontend).arg(&source);
+    if cfg!(target_os = "linux") {
+        build.arg("-ldl");
+    }
+    match build.status() {
+        Ok(status) => assert!(status.success(), "building the stub frontend failed"),
+        Err(err) => {
+            eprintln!("skipped, no C compiler ({}): {}", compiler, err);
+            return;
+        }
+    }
+
+    let output = Command::new(&frontend).arg(core_path()).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}
@@ -0,0 +1,303 @@
+// Python module around the processor, for quick experiments without Rust.
+// The screen comes as bytes, numpy.frombuffer(p.screen, numpy.uint8)
+// .reshape(chip8.SCREEN_HEIGHT, chip8.SCREEN_WIDTH) turns it into an array.
+
+use std::panic::{self, AssertUnwindSafe};
+
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use chip8_rs::engine::{self, Engine};
+use chip8_rs::env::{self as chip8_env, Env};
+use chip8_rs::input::KeyEvent;
+use chip8_rs::platform::{self, Platform};
+use chip8_rs::processor::Processor;
+use chip8_rs::{CYCLES_PER_FRAME, GAME_ENTRY, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// a processor set up like the arguments ask for, and its load address
+fn new_processor(
+    platform: Option<&str>,
+    tickrate: usize,
+    engine: &str,
+) -> PyResult<(Processor, usize)> {
+    let mut processor = Processor::new();
+    let mut load_address = GAME_ENTRY;
+    if let Some(name) = platform {
+        let platform = Platform::from_name(name).ok_or_else(|| {
+            PyValueError::new_err(format!("platform has to be one of {:?}", platform::NAMES))
+        })?;
+        processor.set_platform(platform);
+        load_address = platform.load_address();
+    }
+    let engine = Engine::from_name(engine).ok_or_else(|| {
+        PyValueError::new_err(format!("engine has to be one of {:?}", engine::NAMES))
+    })?;
+    processor.set_engine(engine);
+    if tickrate == 0 {
+        return Err(PyValueError::new_err("tickrate has to be positive"));
+    }
+    Ok((processor, load_address))
+}
+
+fn check_rom_size(processor: &Processor, load_address: usize, rom: &[u8]) -> PyResult<()> {
+    let max = processor.memory().len() - load_address;
+    if rom.len() > max {
+        return Err(PyValueError::new_err(format!(
+            "rom has {} bytes, but only {} fit into memory",
+            rom.len(),
+            max
+        )));
+    }
+    Ok(())
+}
+
+// a rom crashing the processor raises RuntimeError, pyo3's PanicException
+// isn't an Exception and gets past the usual except clauses
+fn catch_crash<T>(run: impl FnOnce() -> T) -> PyResult<T> {
+    panic::catch_unwind(AssertUnwindSafe(run))
+        .map_err(|_| PyRuntimeError::new_err("the rom crashed the processor"))
+}
+
+/// Processor(platform=None, tickrate=10, engine="interpreter")
+///
+/// A chip8 processor without any window. platform is one of "chip8", "schip",
+/// "xochip" or "eti660", tickrate the instructions per frame.
+// stays on the thread creating it, the code buffer of the jit isn't Sync
+#[pyclass(name = "Processor", module = "chip8", unsendable)]
+struct Emulator {
+    processor: Processor,
+    load_address: usize,
+    rom: Vec<u8>,
+    cycles_per_frame: usize,
+}
+
+#[pymethods]
+impl Emulator {
+    #[new]
+    #[pyo3(signature = (platform=None, tickrate=CYCLES_PER_FRAME, engine="interpreter"))]
+    fn new(platform: Option<&str>, tickrate: usize, engine: &str) -> PyResult<Self> {
+        let (processor, load_address) = new_processor(platform, tickrate, engine)?;
+        Ok(Emulator {
+            processor,
+            load_address,
+            rom: Vec::new(),
+            cycles_per_frame: tickrate,
+        })
+    }
+
+    /// Reset the processor and load a rom.
+    fn load_rom(&mut self, rom: &[u8]) -> PyResult<()> {
+        check_rom_size(&self.processor, self.load_address, rom)?;
+        self.rom = rom.to_vec();
+        self.reset();
+        Ok(())
+    }
+
+    /// Start the loaded rom over.
+    fn reset(&mut self) {
+        self.processor.reset();
+        self.processor.load_game(&self.rom);
+    }
+
+    /// Run a number of instructions without ticking the timers,
+    /// True if the screen changed. Raises RuntimeError if the rom crashes.
+    #[pyo3(signature = (cycles=1))]
+    fn step(&mut self, cycles: usize) -> PyResult<bool> {
+        catch_crash(|| self.processor.run(cycles))
+    }
+
+    /// Run a number of 60Hz frames, True if the screen changed.
+    /// Raises RuntimeError if the rom crashes.
+    #[pyo3(signature = (count=1))]
+    fn run_frames(&mut self, count: usize) -> PyResult<bool> {
+        catch_crash(|| {
+            let mut redraw = false;
+            for _ in 0..count {
+                redraw |= self.processor.run_frame(self.cycles_per_frame);
+            }
+            redraw
+        })
+    }
+
+    /// Press or release a key of the hex keypad, 0 to 15.
+    fn set_key(&mut self, key: usize, pressed: bool) -> PyResult<()> {
+        if key >= 16 {
+            return Err(PyValueError::new_err("key has to be between 0 and 15"));
+        }
+        self.processor.key_event(if pressed {
+            KeyEvent::Pressed(key)
+        } else {
+            KeyEvent::Released(key)
+        });
+        Ok(())
+    }
+
+    /// SCREEN_WIDTH * SCREEN_HEIGHT pixels row by row, 0 when off,
+    /// otherwise a bitmask of the planes the pixel is set in.
+    #[getter]
+    fn screen<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        let screen: Vec<u8> = self.processor.screen().iter().flatten().copied().collect();
+        PyBytes::new(py, &screen)
+    }
+
+    /// A copy of the whole memory.
+    #[getter]
+    fn memory<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.processor.memory())
+    }
+
+    /// Copy data into memory starting at address.
+    fn write_memory(&mut self, address: usize, data: &[u8]) -> PyResult<()> {
+        if address.saturating_add(data.len()) > self.processor.memory().len() {
+            return Err(PyValueError::new_err("data doesn't fit into memory"));
+        }
+        for (pos, &value) in data.iter().enumerate() {
+            self.processor.write_memory(address + pos, value);
+        }
+        Ok(())
+    }
+
+    /// V0 to VF.
+    #[getter]
+    fn registers<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.processor.registers())
+    }
+
+    fn set_register(&mut self, x: usize, value: u8) -> PyResult<()> {
+        if x >= 16 {
+            return Err(PyValueError::new_err("register has to be between 0 and 15"));
+        }
+        self.processor.set_register(x, value);
+        Ok(())
+    }
+
+    #[getter]
+    fn index(&self) -> usize {
+        self.processor.index()
+    }
+
+    #[getter]
+    fn pc(&self) -> usize {
+        self.processor.pc()
+    }
+
+    /// The delay and the sound timer.
+    #[getter]
+    fn timers(&self) -> (usize, usize) {
+        self.processor.timers()
+    }
+
+    fn save_state<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.processor.save_state())
+    }
+
+    /// Restore a save state of the same platform, nothing changes on errors.
+    fn load_state(&mut self, state: &[u8]) -> PyResult<()> {
+        self.processor
+            .load_state(state)
+            .map_err(|err| PyValueError::new_err(format!("invalid state: {:?}", err)))
+    }
+}
+
+/// Env(rom, platform=None, tickrate=10, frame_skip=4, engine="interpreter", reward=None, done=None)
+///
+/// A gym like environment to train agents on a rom. Actions are the keys 0 to 15
+/// and NOOP, each step taps the key during frame_skip frames. reward(before, after)
+/// gets the memory before and after the step, done(memory) the one after it.
+/// A rom crashing the processor ends the episode without reward.
+#[pyclass(name = "Env", module = "chip8", unsendable)]
+struct PyEnv {
+    env: Env,
+    reward: Option<Py<PyAny>>,
+    done: Option<Py<PyAny>>,
+}
+
+#[pymethods]
+impl PyEnv {
+    #[new]
+    #[pyo3(signature = (
+        rom,
+        platform=None,
+        tickrate=CYCLES_PER_FRAME,
+        frame_skip=4,
+        engine="interpreter",
+        reward=None,
+        done=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        rom: &[u8],
+        platform: Option<&str>,
+        tickrate: usize,
+        frame_skip: usize,
+        engine: &str,
+        reward: Option<Py<PyAny>>,
+        done: Option<Py<PyAny>>,
+    ) -> PyResult<Self> {
+        let (processor, load_address) = new_processor(platform, tickrate, engine)?;
+        check_rom_size(&processor, load_address, rom)?;
+        let mut env = Env::new(processor, rom);
+        env.set_tickrate(tickrate);
+        env.set_frame_skip(frame_skip);
+        Ok(PyEnv { env, reward, done })
+    }
+
+    /// Start the rom over, returns the first observation.
+    fn reset<'py>(&mut self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.env.reset())
+    }
+
+    /// Returns the observation, which is the screen like Processor.screen, the reward and done.
+    /// done stays True after a crash until reset or restore_state.
+    fn step<'py>(
+        &mut self,
+        py: Python<'py>,
+        action: usize,
+    ) -> PyResult<(Bound<'py, PyBytes>, f64, bool)> {
+        if action >= chip8_env::ACTIONS {
+            return Err(PyValueError::new_err(format!(
+                "action has to be below {}",
+                chip8_env::ACTIONS
+            )));
+        }
+        let before = PyBytes::new(py, self.env.processor().memory());
+        let (observation, _, _) = self.env.step(action);
+        if self.env.crashed() {
+            return Ok((PyBytes::new(py, &observation), 0.0, true));
+        }
+        let after = PyBytes::new(py, self.env.processor().memory());
+
+        let reward = match &self.reward {
+            Some(reward) => reward.call1(py, (before, &after))?.extract(py)?,
+            None => 0.0,
+        };
+        let done = match &self.done {
+            Some(done) => done.call1(py, (&after,))?.extract(py)?,
+            None => false,
+        };
+        Ok((PyBytes::new(py, &observation), reward, done))
+    }
+
+    /// Everything needed to come back to this point, e.g. for tree search.
+    fn clone_state<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.env.clone_state())
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> PyResult<()> {
+        self.env
+            .restore_state(state)
+            .map_err(|err| PyValueError::new_err(format!("invalid state: {:?}", err)))
+    }
+}
+
+#[pymodule]
+fn chip8(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Emulator>()?;
+    module.add_class::<PyEnv>()?;
+    module.add("ACTIONS", chip8_env::ACTIONS)?;
+    module.add("NOOP", chip8_env::NOOP)?;
+    module.add("SCREEN_WIDTH", SCREEN_WIDTH)?;
+    module.add("SCREEN_HEIGHT", SCREEN_HEIGHT)?;
+    Ok(())
+}
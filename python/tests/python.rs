@@ -0,0 +1,43 @@
+// Runs tests/test_chip8.py against the module, skipped without python
+#![cfg(unix)]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_python_module() {
+    // cargo test builds the module into the deps directory of this test, the copy
+    // next to it may be left from an older cargo build, python wants it named after the module
+    let exe = env::current_exe().unwrap();
+    let deps_dir = exe.parent().unwrap();
+    let name = if cfg!(target_os = "macos") {
+        "libchip8_python.dylib"
+    } else {
+        "libchip8_python.so"
+    };
+    let library = deps_dir.join(name);
+    let module_dir = env::temp_dir().join("chip8-python-test");
+    fs::create_dir_all(&module_dir).unwrap();
+    fs::copy(&library, module_dir.join("chip8.so")).unwrap();
+
+    let python = env::var("PYO3_PYTHON").unwrap_or_else(|_| "python3".to_string());
+    let script = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/test_chip8.py");
+    let output = match Command::new(&python)
+        .arg(script)
+        .env("PYTHONPATH", &module_dir)
+        .output()
+    {
+        Ok(output) => output,
+        Err(err) => {
+            eprintln!("skipped, no python ({}): {}", python, err);
+            return;
+        }
+    };
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
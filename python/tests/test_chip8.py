@@ -0,0 +1,99 @@
+# Tests of the python module, run by tests/python.rs or with
+# "python -m unittest" after "maturin develop"
+
+import unittest
+
+import chip8
+
+# draws a 0 at (5, 0), then waits for a key into V3 and loops
+ROM = bytes([0x60, 0x05, 0x61, 0x00, 0xF1, 0x29, 0xD0, 0x15, 0xF3, 0x0A, 0x12, 0x08])
+
+
+class TestProcessor(unittest.TestCase):
+    def setUp(self):
+        self.processor = chip8.Processor(platform="schip")
+        self.processor.load_rom(ROM)
+
+    def test_screen(self):
+        self.assertTrue(self.processor.run_frames())
+        screen = self.processor.screen
+        self.assertEqual(len(screen), chip8.SCREEN_WIDTH * chip8.SCREEN_HEIGHT)
+        self.assertEqual(sum(1 for pixel in screen if pixel), 14)
+        self.assertEqual(screen[5], 1)
+
+    def test_keys_and_registers(self):
+        self.processor.run_frames(2)
+        self.processor.set_key(7, True)
+        self.processor.set_key(7, False)
+        self.processor.run_frames()
+        self.assertEqual(self.processor.registers[3], 7)
+        self.assertEqual(self.processor.pc, 0x20A)
+        with self.assertRaises(ValueError):
+            self.processor.set_key(16, True)
+
+    def test_memory(self):
+        self.processor.write_memory(0x300, b"\xde\xad")
+        self.assertEqual(self.processor.memory[0x300:0x302], b"\xde\xad")
+        self.assertEqual(len(self.processor.memory), 4096)
+        with self.assertRaises(ValueError):
+            self.processor.write_memory(4095, b"\x00\x00")
+
+    def test_save_state(self):
+        self.processor.step(5)
+        state = self.processor.save_state()
+        self.processor.set_register(0, 0x42)
+        self.processor.load_state(state)
+        self.assertEqual(self.processor.registers[0], 5)
+        with self.assertRaises(ValueError):
+            self.processor.load_state(state[:10])
+
+    def test_invalid_arguments(self):
+        with self.assertRaises(ValueError):
+            chip8.Processor(platform="gameboy")
+        with self.assertRaises(ValueError):
+            chip8.Processor(engine="turbo")
+        with self.assertRaises(ValueError):
+            self.processor.load_rom(bytes(4096))
+
+    def test_crash(self):
+        # i := 0xfff, then v0 and v1 are read from behind the end of memory
+        self.processor.load_rom(bytes([0xAF, 0xFF, 0xF1, 0x65]))
+        with self.assertRaises(RuntimeError):
+            self.processor.run_frames()
+
+
+# counts key 5 presses, stores the bcd at 0x300 and waits for the next key
+COUNTER = bytes([0xA3, 0x00, 0xF1, 0x0A, 0x41, 0x05, 0x70, 0x01, 0xF0, 0x33, 0x12, 0x02])
+
+
+class TestEnv(unittest.TestCase):
+    def setUp(self):
+        self.env = chip8.Env(
+            COUNTER,
+            reward=lambda before, after: after[0x302] - before[0x302],
+            done=lambda memory: memory[0x302] == 2,
+        )
+
+    def test_step(self):
+        observation = self.env.reset()
+        self.assertEqual(len(observation), chip8.SCREEN_WIDTH * chip8.SCREEN_HEIGHT)
+        self.assertEqual(self.env.step(chip8.NOOP)[1:], (0.0, False))
+        self.assertEqual(self.env.step(5)[1:], (1.0, False))
+        self.assertEqual(self.env.step(5)[1:], (1.0, True))
+        with self.assertRaises(ValueError):
+            self.env.step(chip8.ACTIONS)
+
+    def test_clone_state(self):
+        self.env.step(chip8.NOOP)
+        state = self.env.clone_state()
+        self.env.step(5)
+        self.env.restore_state(state)
+        self.assertEqual(self.env.step(5)[1], 1.0)
+
+    def test_crash(self):
+        env = chip8.Env(bytes([0xAF, 0xFF, 0xF1, 0x65]), reward=lambda before, after: 1.0)
+        self.assertEqual(env.step(chip8.NOOP)[1:], (0.0, True))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,496 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::instruction::Instruction;
+use crate::platform::Quirks;
+
+// Where execution goes after an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Next,
+    Jump(usize),
+    Call(usize), // and the next one after the return
+    Return,
+    Skip,            // the next one or the one after
+    Computed(usize), // bnnn, nnn plus V0, or plus Vx with the jump_vx quirk
+    Unknown,         // no instruction, the processor steps over it
+}
+
+pub fn flow(opcode: u16) -> Flow {
+    match Instruction::decode(opcode) {
+        Instruction::Return => Flow::Return,
+        Instruction::Jump(nnn) => Flow::Jump(nnn),
+        Instruction::Call(nnn) => Flow::Call(nnn),
+        Instruction::SkipEqual(..)
+        | Instruction::SkipNotEqual(..)
+        | Instruction::SkipEqualRegister(..)
+        | Instruction::SkipNotEqualRegister(..)
+        | Instruction::SkipPressed(_)
+        | Instruction::SkipNotPressed(_) => Flow::Skip,
+        Instruction::JumpOffset(nnn) => Flow::Computed(nnn),
+        Instruction::Unknown => Flow::Unknown,
+        _ => Flow::Next,
+    }
+}
+
+// mnemonics of Cowgod's reference, None for opcodes the processor doesn't know,
+// quirks change which register bnnn adds
+pub fn disassemble(opcode: u16, quirks: Quirks) -> Option<String> {
+    let text = match Instruction::decode(opcode) {
+        Instruction::Clear => "CLS".to_string(),
+        Instruction::Return => "RET".to_string(),
+        Instruction::Jump(nnn) => format!("JP {:#05x}", nnn),
+        Instruction::Call(nnn) => format!("CALL {:#05x}", nnn),
+        Instruction::SkipEqual(x, kk) => format!("SE V{:X}, {:#04x}", x, kk),
+        Instruction::SkipNotEqual(x, kk) => format!("SNE V{:X}, {:#04x}", x, kk),
+        Instruction::SkipEqualRegister(x, y) => format!("SE V{:X}, V{:X}", x, y),
+        Instruction::Load(x, kk) => format!("LD V{:X}, {:#04x}", x, kk),
+        Instruction::Add(x, kk) => format!("ADD V{:X}, {:#04x}", x, kk),
+        Instruction::Move(x, y) => format!("LD V{:X}, V{:X}", x, y),
+        Instruction::Or(x, y) => format!("OR V{:X}, V{:X}", x, y),
+        Instruction::And(x, y) => format!("AND V{:X}, V{:X}", x, y),
+        Instruction::Xor(x, y) => format!("XOR V{:X}, V{:X}", x, y),
+        Instruction::AddRegister(x, y) => format!("ADD V{:X}, V{:X}", x, y),
+        Instruction::Sub(x, y) => format!("SUB V{:X}, V{:X}", x, y),
+        Instruction::ShiftRight(x, y) => format!("SHR V{:X}, V{:X}", x, y),
+        Instruction::SubNegated(x, y) => format!("SUBN V{:X}, V{:X}", x, y),
+        Instruction::ShiftLeft(x, y) => format!("SHL V{:X}, V{:X}", x, y),
+        Instruction::SkipNotEqualRegister(x, y) => format!("SNE V{:X}, V{:X}", x, y),
+        Instruction::LoadIndex(nnn) => format!("LD I, {:#05x}", nnn),
+        Instruction::JumpOffset(nnn) if quirks.jump_vx => {
+            format!("JP V{:X}, {:#05x}", nnn >> 8, nnn)
+        }
+        Instruction::JumpOffset(nnn) => format!("JP V0, {:#05x}", nnn),
+        Instruction::Random(x, kk) => format!("RND V{:X}, {:#04x}", x, kk),
+        Instruction::Draw(x, y, n) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        Instruction::SkipPressed(x) => format!("SKP V{:X}", x),
+        Instruction::SkipNotPressed(x) => format!("SKNP V{:X}", x),
+        Instruction::LoadDelay(x) => format!("LD V{:X}, DT", x),
+        Instruction::WaitKey(x) => format!("LD V{:X}, K", x),
+        Instruction::SetDelay(x) => format!("LD DT, V{:X}", x),
+        Instruction::SetSound(x) => format!("LD ST, V{:X}", x),
+        Instruction::AddIndex(x) => format!("ADD I, V{:X}", x),
+        Instruction::Font(x) => format!("LD F, V{:X}", x),
+        Instruction::BigFont(x) => format!("LD HF, V{:X}", x),
+        Instruction::Bcd(x) => format!("LD B, V{:X}", x),
+        Instruction::Store(x) => format!("LD [I], V{:X}", x),
+        Instruction::Restore(x) => format!("LD V{:X}, [I]", x),
+        Instruction::Unknown => return None,
+    };
+    Some(text)
+}
+
+// What a stretch of the rom seems to be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Code,
+    Data,        // not reached, and pointed at by annn or no valid instructions
+    Unreachable, // not reached, but valid instructions all the way
+}
+
+// Instructions running one after the other, control flow only leaves at the end
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    pub end: usize, // behind the last instruction
+    pub successors: Vec<usize>,
+    pub calls: Vec<usize>,
+}
+
+// Code and data of a rom, found by following every path from its entry without running it.
+// bnnn can't be followed, only its base address nnn is assumed to be a target, which it
+// is with either register at 0, xnn of the jump_vx quirk being nnn.
+pub struct Analysis {
+    rom: Vec<u8>,
+    load_address: usize,
+    quirks: Quirks,        // of the platform the rom runs on
+    code: BTreeSet<usize>, // addresses of reached instructions
+    blocks: BTreeMap<usize, Block>,
+    subroutines: BTreeSet<usize>,
+    computed_jumps: BTreeSet<usize>,
+    unknown: BTreeSet<usize>,    // reached, but no instruction
+    references: BTreeSet<usize>, // annn targets
+    outside: BTreeSet<usize>,    // flow leaving the rom, e.g. into memory written at runtime
+}
+
+impl Analysis {
+    pub fn new(rom: &[u8], load_address: usize, quirks: Quirks) -> Self {
+        let mut analysis = Analysis {
+            rom: rom.to_vec(),
+            load_address,
+            quirks,
+            code: BTreeSet::new(),
+            blocks: BTreeMap::new(),
+            subroutines: BTreeSet::new(),
+            computed_jumps: BTreeSet::new(),
+            unknown: BTreeSet::new(),
+            references: BTreeSet::new(),
+            outside: BTreeSet::new(),
+        };
+        analysis.trace();
+        analysis.split_blocks();
+        analysis
+    }
+
+    fn opcode(&self, address: usize) -> Option<u16> {
+        let pos = address.checked_sub(self.load_address)?;
+        let bytes = self.rom.get(pos..pos + 2)?;
+        Some((bytes[0] as u16) << 8 | bytes[1] as u16)
+    }
+
+    // addresses execution may continue at, calls included
+    fn targets(&self, address: usize) -> Vec<usize> {
+        match self.opcode(address).map(flow) {
+            Some(Flow::Next) | Some(Flow::Unknown) => vec![address + 2],
+            Some(Flow::Jump(target)) | Some(Flow::Computed(target)) => vec![target],
+            Some(Flow::Call(target)) => vec![target, address + 2],
+            Some(Flow::Skip) => vec![address + 2, address + 4],
+            Some(Flow::Return) | None => Vec::new(),
+        }
+    }
+
+    // every instruction reachable from the entry
+    fn trace(&mut self) {
+        let mut pending = vec![self.load_address];
+        while let Some(address) = pending.pop() {
+            if self.code.contains(&address) {
+                continue;
+            }
+            let opcode = match self.opcode(address) {
+                Some(opcode) => opcode,
+                None => {
+                    self.outside.insert(address);
+                    continue;
+                }
+            };
+            self.code.insert(address);
+            match flow(opcode) {
+                Flow::Call(target) => {
+                    self.subroutines.insert(target);
+                }
+                Flow::Computed(_) => {
+                    self.computed_jumps.insert(address);
+                }
+                Flow::Unknown => {
+                    self.unknown.insert(address);
+                }
+                _ => {}
+            }
+            if let Instruction::LoadIndex(nnn) = Instruction::decode(opcode) {
+                self.references.insert(nnn);
+            }
+            pending.extend(self.targets(address));
+        }
+    }
+
+    fn split_blocks(&mut self) {
+        // blocks start at the entry, at targets and behind instructions leaving a straight line
+        let mut leaders = BTreeSet::new();
+        leaders.insert(self.load_address);
+        for &address in self.code.iter() {
+            let opcode = self.opcode(address).unwrap_or(0);
+            match flow(opcode) {
+                Flow::Next | Flow::Unknown => {}
+                Flow::Call(target) => {
+                    leaders.insert(target);
+                }
+                _ => leaders.extend(self.targets(address)),
+            }
+        }
+        leaders.retain(|address| self.code.contains(address));
+
+        for &start in leaders.iter() {
+            let mut block = Block {
+                start,
+                end: start,
+                successors: Vec::new(),
+                calls: Vec::new(),
+            };
+            let mut address = start;
+            loop {
+                let opcode = self.opcode(address).unwrap_or(0);
+                block.end = address + 2;
+                let next = address + 2;
+                match flow(opcode) {
+                    Flow::Call(target) => block.calls.push(target),
+                    Flow::Next | Flow::Unknown => {}
+                    _ => {
+                        block.successors = self.targets(address);
+                        break;
+                    }
+                }
+                if leaders.contains(&next) || !self.code.contains(&next) {
+                    block.successors = vec![next];
+                    break;
+                }
+                address = next;
+            }
+            self.blocks.insert(start, block);
+        }
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.values()
+    }
+
+    pub fn subroutines(&self) -> &BTreeSet<usize> {
+        &self.subroutines
+    }
+
+    pub fn computed_jumps(&self) -> &BTreeSet<usize> {
+        &self.computed_jumps
+    }
+
+    pub fn unknown(&self) -> &BTreeSet<usize> {
+        &self.unknown
+    }
+
+    pub fn outside(&self) -> &BTreeSet<usize> {
+        &self.outside
+    }
+
+    // the whole rom, split into code, data and unreachable code
+    pub fn regions(&self) -> Vec<(Range<usize>, Region)> {
+        if self.rom.is_empty() {
+            return Vec::new();
+        }
+        let end = self.load_address + self.rom.len();
+        let mut covered = vec![false; self.rom.len()];
+        for &address in self.code.iter() {
+            let pos = address - self.load_address;
+            covered[pos] = true;
+            if pos + 1 < covered.len() {
+                covered[pos + 1] = true;
+            }
+        }
+
+        let mut regions: Vec<(Range<usize>, Region)> = Vec::new();
+        let mut start = self.load_address;
+        for address in self.load_address..=end {
+            let pos = address - self.load_address;
+            let split = address == end
+                || (address > start
+                    && (covered[pos] != covered[pos - 1] || self.references.contains(&address)));
+            if split {
+                let region = if covered[start - self.load_address] {
+                    Region::Code
+                } else {
+                    self.guess(start..address)
+                };
+                regions.push((start..address, region));
+                start = address;
+            }
+        }
+        regions
+    }
+
+    // data is pointed at, or doesn't decode, everything else is just never reached
+    fn guess(&self, range: Range<usize>) -> Region {
+        let referenced = range
+            .clone()
+            .any(|address| self.references.contains(&address));
+        let decodes = range.len() % 2 == 0
+            && range.clone().step_by(2).all(|address| {
+                self.opcode(address)
+                    .and_then(|opcode| disassemble(opcode, self.quirks))
+                    .is_some()
+            });
+        if referenced || !decodes {
+            Region::Data
+        } else {
+            Region::Unreachable
+        }
+    }
+
+    // an assembler like listing, code with labels and data as bytes
+    pub fn listing(&self) -> String {
+        let mut text = String::new();
+        for (range, region) in self.regions() {
+            match region {
+                Region::Code => {
+                    for address in range.step_by(2) {
+                        if !self.code.contains(&address) {
+                            continue;
+                        }
+                        if self.subroutines.contains(&address) {
+                            let _ = writeln!(text, "\nsub_{:03x}:", address);
+                        } else if self.blocks.contains_key(&address) {
+                            let _ = writeln!(text, "\nlabel_{:03x}:", address);
+                        }
+                        let _ = writeln!(text, "    {}", self.instruction(address));
+                    }
+                }
+                // decodes, so shown as instructions nobody runs
+                Region::Unreachable => {
+                    let _ = writeln!(text, "\nunreachable_{:03x}:", range.start);
+                    for address in range.step_by(2) {
+                        let _ = writeln!(text, "    {}", self.instruction(address));
+                    }
+                }
+                Region::Data => {
+                    let _ = writeln!(text, "\ndata_{:03x}:", range.start);
+                    for row in range.clone().step_by(8) {
+                        let bytes: Vec<String> = (row..(row + 8).min(range.end))
+                            .map(|address| format!("{:02x}", self.rom[address - self.load_address]))
+                            .collect();
+                        let _ = writeln!(text, "    {:#05x}  {}", row, bytes.join(" "));
+                    }
+                }
+            }
+        }
+        text
+    }
+
+    fn instruction(&self, address: usize) -> String {
+        let opcode = self.opcode(address).unwrap_or(0);
+        let text = disassemble(opcode, self.quirks).unwrap_or_else(|| "???".to_string());
+        let note = match flow(opcode) {
+            Flow::Computed(_) => "  ; computed jump",
+            Flow::Unknown => "  ; unknown opcode",
+            _ => "",
+        };
+        format!("{:#05x}  {:04x}  {}{}", address, opcode, text, note)
+    }
+
+    // Graphviz control flow graph, calls dashed, computed jumps red
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        dot.push_str("digraph cfg {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for address in (block.start..block.end).step_by(2) {
+                label.push_str(&self.instruction(address));
+                label.push_str("\\l");
+            }
+            let mut style = String::new();
+            if self.subroutines.contains(&block.start) {
+                style.push_str(", peripheries=2");
+            }
+            if self.computed_jumps.contains(&(block.end - 2)) {
+                style.push_str(", color=red");
+            }
+            let _ = writeln!(
+                dot,
+                "    n{:03x} [label=\"{}\"{}];",
+                block.start, label, style
+            );
+
+            for &target in block.successors.iter() {
+                let _ = writeln!(dot, "    n{:03x} -> n{:03x};", block.start, target);
+            }
+            for &target in block.calls.iter() {
+                let _ = writeln!(
+                    dot,
+                    "    n{:03x} -> n{:03x} [style=dashed];",
+                    block.start, target
+                );
+            }
+        }
+        // flow leaving the rom ends in a node of its own
+        for &address in self.outside.iter() {
+            let _ = writeln!(
+                dot,
+                "    n{:03x} [label=\"{:#05x}  outside of the rom\", style=dotted];",
+                address, address
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROM: [u8; 20] = [
+        0x22, 0x0a, // 200: call 20a
+        0x30, 0x00, // 202: skip if V0 == 0
+        0x12, 0x00, // 204: jump 200
+        0x12, 0x06, // 206: jump 206
+        0x00, 0xe0, // 208: never reached
+        0xa2, 0x10, // 20a: I = 210
+        0xd0, 0x01, // 20c: draw
+        0x00, 0xee, // 20e: return
+        0xff, 0x00, // 210: sprite
+        0x00, 0x00, // 212: padding
+    ];
+
+    #[test]
+    fn test_flow() {
+        assert_eq!(flow(0x00ee), Flow::Return);
+        assert_eq!(flow(0x1234), Flow::Jump(0x234));
+        assert_eq!(flow(0x2345), Flow::Call(0x345));
+        assert_eq!(flow(0xe19e), Flow::Skip);
+        assert_eq!(flow(0x5121), Flow::Unknown);
+        assert_eq!(flow(0xb300), Flow::Computed(0x300));
+        assert_eq!(flow(0xf165), Flow::Next);
+        let quirks = Quirks::default();
+        assert_eq!(disassemble(0xd125, quirks).unwrap(), "DRW V1, V2, 5");
+        assert_eq!(disassemble(0x0123, quirks), None);
+        assert_eq!(disassemble(0xb300, quirks).unwrap(), "JP V0, 0x300");
+        let quirks = Quirks {
+            jump_vx: true,
+            ..quirks
+        };
+        assert_eq!(disassemble(0xb300, quirks).unwrap(), "JP V3, 0x300");
+    }
+
+    #[test]
+    fn test_blocks() {
+        let analysis = Analysis::new(&ROM, 0x200, Quirks::default());
+        let blocks: Vec<(usize, usize, Vec<usize>)> = analysis
+            .blocks()
+            .map(|block| (block.start, block.end, block.successors.clone()))
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![
+                (0x200, 0x204, vec![0x204, 0x206]),
+                (0x204, 0x206, vec![0x200]),
+                (0x206, 0x208, vec![0x206]),
+                (0x20a, 0x210, vec![]),
+            ]
+        );
+        assert_eq!(analysis.blocks().next().unwrap().calls, vec![0x20a]);
+        assert!(analysis.subroutines().contains(&0x20a));
+    }
+
+    #[test]
+    fn test_regions() {
+        let analysis = Analysis::new(&ROM, 0x200, Quirks::default());
+        assert_eq!(
+            analysis.regions(),
+            vec![
+                (0x200..0x208, Region::Code),
+                (0x208..0x20a, Region::Unreachable),
+                (0x20a..0x210, Region::Code),
+                (0x210..0x214, Region::Data),
+            ]
+        );
+        assert!(analysis
+            .listing()
+            .contains("sub_20a:\n    0x20a  a210  LD I, 0x210"));
+    }
+
+    #[test]
+    fn test_computed_jumps() {
+        // jump table at 0x204, only its first entry gets followed
+        let rom = [
+            0xb2, 0x04, 0x00, 0x00, 0x12, 0x08, 0x12, 0x0a, 0x12, 0x08, 0x12, 0x0a,
+        ];
+        let analysis = Analysis::new(&rom, 0x200, Quirks::default());
+        assert!(analysis.computed_jumps().contains(&0x200));
+        assert_eq!(analysis.regions()[3], (0x206..0x208, Region::Unreachable));
+        assert!(analysis.to_dot().contains(
+            "n200 [label=\"0x200  b204  JP V0, 0x204  ; computed jump\\l\", color=red];"
+        ));
+
+        // falling off the end
+        let analysis = Analysis::new(&[0x60, 0x00], 0x200, Quirks::default());
+        assert!(analysis.outside().contains(&0x202));
+        assert!(analysis.to_dot().contains("n200 -> n202;"));
+    }
+}
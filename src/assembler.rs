@@ -0,0 +1,948 @@
+// Assembles Octo source, which Octo cartridges carry instead of the program bytes.
+// Knows the instructions of chip8, schip and xo-chip, labels, :const, :alias,
+// :macro, :calc, :byte, :org, :next, :unpack and the if, loop and while statements.
+// Like in Octo, programs start with a jump to the label main, placed at the
+// origin where the rom gets loaded.
+
+use std::collections::{HashMap, VecDeque};
+use std::f64::consts;
+
+const MAX_ADDRESS: usize = 0xFFFF;
+// macros calling themselves would expand forever
+const MAX_EXPANSIONS: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssemblerError {
+    pub line: usize, // counting from 1
+    pub message: String,
+}
+
+#[derive(Clone)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+// How a name used before its definition gets filled in at the end
+#[derive(Clone, Copy)]
+enum Fixup {
+    Address,    // low 12 bits of the instruction
+    Long,       // all 16 bits, following i := long
+    Unpack(u8), // nibble and 12 bits into the instructions setting v0 and v1
+    UnpackLong,
+}
+
+struct Reference {
+    at: usize,
+    fixup: Fixup,
+    name: String,
+    line: usize,
+}
+
+struct Macro {
+    arguments: Vec<String>,
+    body: Vec<Token>,
+    calls: usize,
+}
+
+struct Assembler {
+    tokens: VecDeque<Token>,
+    line: usize, // of the last token taken
+    rom: Vec<u8>,
+    origin: usize, // address of the first byte of the rom
+    here: usize,
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    expansions: usize,
+    references: Vec<Reference>,
+    branches: Vec<usize>, // jumps of begin and else, waiting for their target
+    loops: Vec<(usize, Vec<usize>)>, // start and the jumps of while
+}
+
+pub fn assemble(source: &str, origin: usize) -> Result<Vec<u8>, AssemblerError> {
+    let mut assembler = Assembler {
+        tokens: tokenize(source),
+        line: 1,
+        rom: Vec::new(),
+        origin,
+        here: origin,
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        macros: HashMap::new(),
+        expansions: 0,
+        references: Vec::new(),
+        branches: Vec::new(),
+        loops: Vec::new(),
+    };
+    assembler.program()?;
+    Ok(assembler.rom)
+}
+
+// tokens are split by whitespace, strings in quotes stay together, # starts a comment
+fn tokenize(source: &str) -> VecDeque<Token> {
+    let mut tokens = VecDeque::new();
+    for (number, line) in source.lines().enumerate() {
+        let mut rest = line.trim_start();
+        while !rest.is_empty() && !rest.starts_with('#') {
+            let end = match rest.strip_prefix('"') {
+                Some(quoted) => quoted.find('"').map_or(rest.len(), |end| end + 2),
+                None => rest.find(char::is_whitespace).unwrap_or(rest.len()),
+            };
+            tokens.push_back(Token {
+                text: rest[..end].to_string(),
+                line: number + 1,
+            });
+            rest = rest[end..].trim_start();
+        }
+    }
+    tokens
+}
+
+// decimal, hex and binary literals, negative ones too
+fn number(text: &str) -> Option<f64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value } as f64)
+}
+
+impl Assembler {
+    fn error<T>(&self, message: String) -> Result<T, AssemblerError> {
+        Err(AssemblerError {
+            line: self.line,
+            message,
+        })
+    }
+
+    fn next(&mut self) -> Result<String, AssemblerError> {
+        match self.tokens.pop_front() {
+            Some(token) => {
+                self.line = token.line;
+                Ok(token.text)
+            }
+            None => self.error("the program ends early".to_string()),
+        }
+    }
+
+    fn peek(&self, ahead: usize) -> Option<&str> {
+        self.tokens.get(ahead).map(|token| token.text.as_str())
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AssemblerError> {
+        let token = self.next()?;
+        if token != text {
+            return self.error(format!("expected {} instead of {}", text, token));
+        }
+        Ok(())
+    }
+
+    fn program(&mut self) -> Result<(), AssemblerError> {
+        // room for the jump to main
+        self.instruction(0x0000)?;
+        while !self.tokens.is_empty() {
+            let token = self.next()?;
+            self.statement(token)?;
+        }
+        if !self.branches.is_empty() {
+            return self.error("begin without end".to_string());
+        }
+        if !self.loops.is_empty() {
+            return self.error("loop without again".to_string());
+        }
+        self.resolve()?;
+        match self.labels.get("main") {
+            Some(&main) => self.patch_jump(self.origin, main),
+            None => self.error("the label main is missing".to_string()),
+        }
+    }
+
+    fn statement(&mut self, token: String) -> Result<(), AssemblerError> {
+        match token.as_str() {
+            ":" => {
+                let name = self.next()?;
+                self.define(name, self.here)
+            }
+            ":next" => {
+                // the second byte of the next instruction, for self modifying code
+                let name = self.next()?;
+                self.define(name, self.here + 1)
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.known_value()?;
+                self.constants.insert(name, value);
+                Ok(())
+            }
+            ":calc" => {
+                let name = self.next()?;
+                let value = self.calc()?;
+                self.constants.insert(name, value);
+                Ok(())
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+                Ok(())
+            }
+            ":org" => {
+                let address = self.known_value()? as i64;
+                if address < self.origin as i64 || address > MAX_ADDRESS as i64 {
+                    return self.error(format!("{:#x} is outside of the program", address));
+                }
+                self.here = address as usize;
+                Ok(())
+            }
+            ":byte" => {
+                let value = if self.peek(0) == Some("{") {
+                    self.calc()?
+                } else {
+                    self.known_value()?
+                } as i64;
+                if !(-128..=255).contains(&value) {
+                    return self.error(format!("{} doesn't fit into a byte", value));
+                }
+                self.emit(value as u8)
+            }
+            ":unpack" => self.unpack(),
+            ":call" => {
+                let target = self.target(Fixup::Address)?;
+                self.instruction(0x2000 | target)
+            }
+            ":macro" => self.define_macro(),
+            ":assert" => {
+                let message = match self.peek(0) {
+                    Some(text) if text.starts_with('"') => self.next()?,
+                    _ => "assertion failed".to_string(),
+                };
+                if self.calc()? == 0.0 {
+                    return self.error(message.trim_matches('"').to_string());
+                }
+                Ok(())
+            }
+            // debugger settings, which mean nothing here
+            ":breakpoint" | ":proto" => self.next().map(|_| ()),
+            ":monitor" => {
+                self.next()?;
+                self.next().map(|_| ())
+            }
+            ";" | "return" => self.instruction(0x00EE),
+            "clear" => self.instruction(0x00E0),
+            "scroll-right" => self.instruction(0x00FB),
+            "scroll-left" => self.instruction(0x00FC),
+            "exit" => self.instruction(0x00FD),
+            "lores" => self.instruction(0x00FE),
+            "hires" => self.instruction(0x00FF),
+            "audio" => self.instruction(0xF002),
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.instruction(0x00C0 | n)
+            }
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.instruction(0x00D0 | n)
+            }
+            "plane" => {
+                let n = self.nibble()?;
+                self.instruction(0xF001 | n << 8)
+            }
+            "bcd" | "saveflags" | "loadflags" => {
+                let x = (self.register()? as u16) << 8;
+                let opcode = match token.as_str() {
+                    "bcd" => 0xF033,
+                    "saveflags" => 0xF075,
+                    _ => 0xF085,
+                };
+                self.instruction(opcode | x)
+            }
+            "save" | "load" => {
+                let x = (self.register()? as u16) << 8;
+                let save = token == "save";
+                if self.peek(0) == Some("-") {
+                    self.next()?;
+                    let y = (self.register()? as u16) << 4;
+                    self.instruction(if save { 0x5002 } else { 0x5003 } | x | y)
+                } else {
+                    self.instruction(if save { 0xF055 } else { 0xF065 } | x)
+                }
+            }
+            "sprite" => {
+                let x = (self.register()? as u16) << 8;
+                let y = (self.register()? as u16) << 4;
+                let n = self.nibble()?;
+                self.instruction(0xD000 | x | y | n)
+            }
+            "jump" | "jump0" | "native" => {
+                let target = self.target(Fixup::Address)?;
+                let opcode = match token.as_str() {
+                    "jump" => 0x1000,
+                    "jump0" => 0xB000,
+                    _ => 0x0000,
+                };
+                self.instruction(opcode | target)
+            }
+            "i" => self.index(),
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = (self.register()? as u16) << 8;
+                let opcode = match token.as_str() {
+                    "delay" => 0xF015,
+                    "buzzer" => 0xF018,
+                    _ => 0xF03A,
+                };
+                self.instruction(opcode | x)
+            }
+            "if" => {
+                // key and -key have no right hand side
+                let ahead = match self.peek(1) {
+                    Some("key") | Some("-key") => 2,
+                    _ => 3,
+                };
+                match self.peek(ahead) {
+                    Some("then") => {
+                        self.condition(false)?;
+                        self.expect("then")
+                    }
+                    Some("begin") => {
+                        // jump to else or end, unless the condition holds
+                        self.condition(true)?;
+                        self.expect("begin")?;
+                        self.branches.push(self.here);
+                        self.instruction(0x1000)
+                    }
+                    _ => self.error("if needs then or begin".to_string()),
+                }
+            }
+            "else" => {
+                let begin = match self.branches.pop() {
+                    Some(begin) => begin,
+                    None => return self.error("else without begin".to_string()),
+                };
+                self.branches.push(self.here);
+                self.instruction(0x1000)?;
+                self.patch_jump(begin, self.here)
+            }
+            "end" => match self.branches.pop() {
+                Some(jump) => self.patch_jump(jump, self.here),
+                None => self.error("end without begin".to_string()),
+            },
+            "loop" => {
+                self.loops.push((self.here, Vec::new()));
+                Ok(())
+            }
+            "while" => {
+                if self.loops.is_empty() {
+                    return self.error("while outside of a loop".to_string());
+                }
+                // leave the loop, unless the condition holds
+                self.condition(true)?;
+                let jump = self.here;
+                self.instruction(0x1000)?;
+                if let Some((_, exits)) = self.loops.last_mut() {
+                    exits.push(jump);
+                }
+                Ok(())
+            }
+            "again" => {
+                let (start, exits) = match self.loops.pop() {
+                    Some(found) => found,
+                    None => return self.error("again without loop".to_string()),
+                };
+                self.instruction(0x1000 | start as u16)?;
+                for exit in exits {
+                    self.patch_jump(exit, self.here)?;
+                }
+                Ok(())
+            }
+            _ => {
+                if let Some(x) = self.register_of(&token) {
+                    return self.assignment(x);
+                }
+                if self.macros.contains_key(&token) {
+                    return self.expand(&token);
+                }
+                // numbers and constants are data, anything else calls a subroutine
+                if let Some(value) = number(&token).or_else(|| self.constants.get(&token).copied())
+                {
+                    let value = value as i64;
+                    if !(-128..=255).contains(&value) {
+                        return self.error(format!("{} doesn't fit into a byte", token));
+                    }
+                    return self.emit(value as u8);
+                }
+                let target = self.reference(token, Fixup::Address)?;
+                self.instruction(0x2000 | target)
+            }
+        }
+    }
+
+    fn define(&mut self, name: String, address: usize) -> Result<(), AssemblerError> {
+        if self.register_of(&name).is_some() || number(&name).is_some() {
+            return self.error(format!("{} can't be a label", name));
+        }
+        if self.labels.contains_key(&name) {
+            return self.error(format!("the label {} is defined twice", name));
+        }
+        self.labels.insert(name, address);
+        Ok(())
+    }
+
+    fn emit(&mut self, byte: u8) -> Result<(), AssemblerError> {
+        if self.here > MAX_ADDRESS {
+            return self.error("the program doesn't fit into memory".to_string());
+        }
+        let pos = self.here - self.origin;
+        if pos >= self.rom.len() {
+            self.rom.resize(pos + 1, 0);
+        }
+        self.rom[pos] = byte;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn instruction(&mut self, opcode: u16) -> Result<(), AssemblerError> {
+        self.emit((opcode >> 8) as u8)?;
+        self.emit(opcode as u8)
+    }
+
+    // point the jump at address to target
+    fn patch_jump(&mut self, address: usize, target: usize) -> Result<(), AssemblerError> {
+        if target > 0xFFF {
+            return self.error(format!("{:#x} is too far away to jump to", target));
+        }
+        let pos = address - self.origin;
+        self.rom[pos] = 0x10 | (target >> 8) as u8;
+        self.rom[pos + 1] = target as u8;
+        Ok(())
+    }
+
+    fn register_of(&self, text: &str) -> Option<u8> {
+        if let Some(&register) = self.aliases.get(text) {
+            return Some(register);
+        }
+        let digit = text.strip_prefix('v').or_else(|| text.strip_prefix('V'))?;
+        match digit.len() {
+            1 => u8::from_str_radix(digit, 16).ok(),
+            _ => None,
+        }
+    }
+
+    fn register(&mut self) -> Result<u8, AssemblerError> {
+        let token = self.next()?;
+        match self.register_of(&token) {
+            Some(register) => Ok(register),
+            None => self.error(format!("expected a register instead of {}", token)),
+        }
+    }
+
+    // takes the next token, if it is a register
+    fn optional_register(&mut self) -> Result<Option<u8>, AssemblerError> {
+        match self.peek(0).and_then(|token| self.register_of(token)) {
+            Some(register) => self.next().map(|_| Some(register)),
+            None => Ok(None),
+        }
+    }
+
+    fn alias(&self, name: &str, default: u8) -> u16 {
+        self.aliases.get(name).copied().unwrap_or(default) as u16
+    }
+
+    fn known(&self, text: &str) -> Option<f64> {
+        number(text)
+            .or_else(|| self.constants.get(text).copied())
+            .or_else(|| self.labels.get(text).map(|&address| address as f64))
+    }
+
+    fn known_value(&mut self) -> Result<f64, AssemblerError> {
+        let token = self.next()?;
+        match self.known(&token) {
+            Some(value) => Ok(value),
+            None => self.error(format!("{} isn't known yet", token)),
+        }
+    }
+
+    fn value(&mut self, min: i64, max: i64) -> Result<i64, AssemblerError> {
+        let value = self.known_value()? as i64;
+        if value < min || value > max {
+            return self.error(format!("{} is out of range", value));
+        }
+        Ok(value)
+    }
+
+    fn byte(&mut self) -> Result<u16, AssemblerError> {
+        self.value(-128, 255).map(|value| value as u8 as u16)
+    }
+
+    fn nibble(&mut self) -> Result<u16, AssemblerError> {
+        self.value(0, 15).map(|value| value as u16)
+    }
+
+    // an address, names not defined yet are filled in at the end
+    fn target(&mut self, fixup: Fixup) -> Result<u16, AssemblerError> {
+        let token = self.next()?;
+        self.reference(token, fixup)
+    }
+
+    fn reference(&mut self, token: String, fixup: Fixup) -> Result<u16, AssemblerError> {
+        let max = match fixup {
+            Fixup::Long | Fixup::UnpackLong => MAX_ADDRESS as i64,
+            Fixup::Address | Fixup::Unpack(_) => 0xFFF,
+        };
+        if let Some(value) = self.known(&token) {
+            let value = value as i64;
+            if value < 0 || value > max {
+                return self.error(format!("{} is out of range", token));
+            }
+            return Ok(value as u16);
+        }
+        if self.register_of(&token).is_some() {
+            return self.error(format!("expected an address instead of {}", token));
+        }
+        self.references.push(Reference {
+            at: self.here,
+            fixup,
+            name: token,
+            line: self.line,
+        });
+        Ok(0)
+    }
+
+    fn resolve(&mut self) -> Result<(), AssemblerError> {
+        for reference in std::mem::take(&mut self.references) {
+            self.line = reference.line;
+            let target = match self.labels.get(&reference.name) {
+                Some(&target) => target,
+                None => return self.error(format!("{} is never defined", reference.name)),
+            };
+            let pos = reference.at - self.origin;
+            match reference.fixup {
+                Fixup::Address | Fixup::Unpack(_) if target > 0xFFF => {
+                    return self.error(format!("{} is out of range", reference.name));
+                }
+                Fixup::Address => {
+                    self.rom[pos] |= (target >> 8) as u8;
+                    self.rom[pos + 1] = target as u8;
+                }
+                Fixup::Long => {
+                    self.rom[pos] = (target >> 8) as u8;
+                    self.rom[pos + 1] = target as u8;
+                }
+                Fixup::Unpack(nibble) => {
+                    self.rom[pos + 1] = nibble << 4 | (target >> 8) as u8;
+                    self.rom[pos + 3] = target as u8;
+                }
+                Fixup::UnpackLong => {
+                    self.rom[pos + 1] = (target >> 8) as u8;
+                    self.rom[pos + 3] = target as u8;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn index(&mut self) -> Result<(), AssemblerError> {
+        let operator = self.next()?;
+        match operator.as_str() {
+            ":=" => match self.peek(0) {
+                Some("hex") | Some("bighex") => {
+                    let opcode = if self.next()? == "hex" {
+                        0xF029
+                    } else {
+                        0xF030
+                    };
+                    let x = (self.register()? as u16) << 8;
+                    self.instruction(opcode | x)
+                }
+                Some("long") => {
+                    self.next()?;
+                    self.instruction(0xF000)?;
+                    let target = self.target(Fixup::Long)?;
+                    self.instruction(target)
+                }
+                _ => {
+                    let target = self.target(Fixup::Address)?;
+                    self.instruction(0xA000 | target)
+                }
+            },
+            "+=" => {
+                let x = (self.register()? as u16) << 8;
+                self.instruction(0xF01E | x)
+            }
+            _ => self.error(format!("i can't be used with {}", operator)),
+        }
+    }
+
+    fn assignment(&mut self, x: u8) -> Result<(), AssemblerError> {
+        let x = (x as u16) << 8;
+        let operator = self.next()?;
+        let opcode = match operator.as_str() {
+            ":=" => match self.peek(0) {
+                Some("key") => self.next().map(|_| 0xF00A | x)?,
+                Some("delay") => self.next().map(|_| 0xF007 | x)?,
+                Some("random") => {
+                    self.next()?;
+                    0xC000 | x | self.byte()?
+                }
+                _ => match self.optional_register()? {
+                    Some(y) => 0x8000 | x | (y as u16) << 4,
+                    None => 0x6000 | x | self.byte()?,
+                },
+            },
+            "+=" => match self.optional_register()? {
+                Some(y) => 0x8004 | x | (y as u16) << 4,
+                None => 0x7000 | x | self.byte()?,
+            },
+            "-=" => match self.optional_register()? {
+                Some(y) => 0x8005 | x | (y as u16) << 4,
+                None => 0x7000 | x | (self.byte()? as u8).wrapping_neg() as u16,
+            },
+            "=-" | "|=" | "&=" | "^=" | ">>=" | "<<=" => {
+                let y = (self.register()? as u16) << 4;
+                let opcode = match operator.as_str() {
+                    "=-" => 0x8007,
+                    "|=" => 0x8001,
+                    "&=" => 0x8002,
+                    "^=" => 0x8003,
+                    ">>=" => 0x8006,
+                    _ => 0x800E,
+                };
+                opcode | x | y
+            }
+            _ => return self.error(format!("unknown operator {}", operator)),
+        };
+        self.instruction(opcode)
+    }
+
+    // skip the next instruction unless the condition holds, or if it holds when negated
+    fn condition(&mut self, negated: bool) -> Result<(), AssemblerError> {
+        let x = self.register()? as u16;
+        let mut operator = self.next()?;
+        if negated {
+            let inverse = match operator.as_str() {
+                "==" => "!=",
+                "!=" => "==",
+                "key" => "-key",
+                "-key" => "key",
+                "<" => ">=",
+                ">" => "<=",
+                ">=" => "<",
+                "<=" => ">",
+                _ => return self.error(format!("unknown comparison {}", operator)),
+            };
+            operator = inverse.to_string();
+        }
+        match operator.as_str() {
+            "key" => self.instruction(0xE0A1 | x << 8),
+            "-key" => self.instruction(0xE09E | x << 8),
+            "==" | "!=" => {
+                let equal = operator == "==";
+                let opcode = match self.optional_register()? {
+                    Some(y) => (if equal { 0x9000 } else { 0x5000 }) | x << 8 | (y as u16) << 4,
+                    None => (if equal { 0x4000 } else { 0x3000 }) | x << 8 | self.byte()?,
+                };
+                self.instruction(opcode)
+            }
+            "<" | ">" | "<=" | ">=" => {
+                // subtract in vf, the flag tells which one is bigger
+                let temp = self.alias("compare-temp", 0xF) << 8;
+                match self.optional_register()? {
+                    Some(y) => self.instruction(0x8000 | temp | (y as u16) << 4)?,
+                    None => {
+                        let value = self.byte()?;
+                        self.instruction(0x6000 | temp | value)?
+                    }
+                }
+                let subtract = match operator.as_str() {
+                    ">" | "<=" => 0x8005,
+                    _ => 0x8007,
+                };
+                self.instruction(subtract | temp | x << 4)?;
+                let skip = match operator.as_str() {
+                    ">" | "<" => 0x3001,
+                    _ => 0x4001,
+                };
+                self.instruction(skip | temp)
+            }
+            _ => self.error(format!("unknown comparison {}", operator)),
+        }
+    }
+
+    // :unpack nibble label sets v0 and v1 to the nibble and the 12 bits of the address
+    fn unpack(&mut self) -> Result<(), AssemblerError> {
+        let fixup = if self.peek(0) == Some("long") {
+            self.next()?;
+            Fixup::UnpackLong
+        } else {
+            Fixup::Unpack(self.nibble()? as u8)
+        };
+        let target = self.target(fixup)?;
+        let high = match fixup {
+            Fixup::Unpack(nibble) => (nibble as u16) << 4 | target >> 8,
+            _ => target >> 8,
+        };
+        let high_register = self.alias("unpack-hi", 0) << 8;
+        let low_register = self.alias("unpack-lo", 1) << 8;
+        self.instruction(0x6000 | high_register | high)?;
+        self.instruction(0x6000 | low_register | target & 0xFF)
+    }
+
+    fn define_macro(&mut self) -> Result<(), AssemblerError> {
+        let name = self.next()?;
+        let mut arguments = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token == "{" {
+                break;
+            }
+            arguments.push(token);
+        }
+        let body = self.block()?;
+        self.macros.insert(
+            name,
+            Macro {
+                arguments,
+                body,
+                calls: 0,
+            },
+        );
+        Ok(())
+    }
+
+    // the tokens up to the matching }, after a {
+    fn block(&mut self) -> Result<Vec<Token>, AssemblerError> {
+        let mut depth = 1;
+        let mut body = Vec::new();
+        loop {
+            let token = match self.tokens.pop_front() {
+                Some(token) => token,
+                None => return self.error("{ without }".to_string()),
+            };
+            self.line = token.line;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                return Ok(body);
+            }
+            body.push(token);
+        }
+    }
+
+    // put the body in front of the remaining tokens, with the arguments filled in
+    fn expand(&mut self, name: &str) -> Result<(), AssemblerError> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return self.error(format!("macro {} expands too often", name));
+        }
+        let arguments = self.macros[name].arguments.clone();
+        let mut values = HashMap::new();
+        for argument in arguments {
+            let value = self.next()?;
+            values.insert(argument, value);
+        }
+        let called = &mut self.macros.get_mut(name).unwrap();
+        values.insert("CALLS".to_string(), called.calls.to_string());
+        called.calls += 1;
+        for token in called.body.iter().rev() {
+            self.tokens.push_front(Token {
+                text: values.get(&token.text).unwrap_or(&token.text).clone(),
+                line: token.line,
+            });
+        }
+        Ok(())
+    }
+
+    // { expression }, operators go from right to left without precedence like in Octo
+    fn calc(&mut self) -> Result<f64, AssemblerError> {
+        self.expect("{")?;
+        let tokens: Vec<String> = self.block()?.into_iter().map(|token| token.text).collect();
+        let mut pos = 0;
+        let value = self.expression(&tokens, &mut pos)?;
+        match tokens.get(pos) {
+            Some(token) => self.error(format!("unexpected {}", token)),
+            None => Ok(value),
+        }
+    }
+
+    fn expression(&self, tokens: &[String], pos: &mut usize) -> Result<f64, AssemblerError> {
+        let left = self.term(tokens, pos)?;
+        let operator = match tokens.get(*pos) {
+            Some(operator) if operator != ")" => operator,
+            _ => return Ok(left),
+        };
+        *pos += 1;
+        let right = self.expression(tokens, pos)?;
+        let (a, b) = (left as i64, right as i64);
+        let value = match operator.as_str() {
+            "+" => left + right,
+            "-" => left - right,
+            "*" => left * right,
+            "/" => left / right,
+            "%" => left % right,
+            "pow" => left.powf(right),
+            "min" => left.min(right),
+            "max" => left.max(right),
+            "&" => (a & b) as f64,
+            "|" => (a | b) as f64,
+            "^" => (a ^ b) as f64,
+            "<<" => a.wrapping_shl(b as u32) as f64,
+            ">>" => a.wrapping_shr(b as u32) as f64,
+            "<" => (left < right) as u8 as f64,
+            ">" => (left > right) as u8 as f64,
+            "<=" => (left <= right) as u8 as f64,
+            ">=" => (left >= right) as u8 as f64,
+            "==" => (left == right) as u8 as f64,
+            "!=" => (left != right) as u8 as f64,
+            _ => return self.error(format!("unknown operator {}", operator)),
+        };
+        Ok(value)
+    }
+
+    fn term(&self, tokens: &[String], pos: &mut usize) -> Result<f64, AssemblerError> {
+        let token = match tokens.get(*pos) {
+            Some(token) => token.as_str(),
+            None => return self.error("the expression ends early".to_string()),
+        };
+        *pos += 1;
+        let value = match token {
+            "(" => {
+                let value = self.expression(tokens, pos)?;
+                if tokens.get(*pos).map(String::as_str) != Some(")") {
+                    return self.error("( without )".to_string());
+                }
+                *pos += 1;
+                value
+            }
+            "-" | "~" | "!" | "floor" | "ceil" | "abs" | "sqrt" | "sin" | "cos" | "tan" | "exp"
+            | "log" | "sign" | "@" => {
+                let value = self.term(tokens, pos)?;
+                match token {
+                    "-" => -value,
+                    "~" => !(value as i64) as f64,
+                    "!" => (value == 0.0) as u8 as f64,
+                    "floor" => value.floor(),
+                    "ceil" => value.ceil(),
+                    "abs" => value.abs(),
+                    "sqrt" => value.sqrt(),
+                    "sin" => value.sin(),
+                    "cos" => value.cos(),
+                    "tan" => value.tan(),
+                    "exp" => value.exp(),
+                    "log" => value.ln(),
+                    "sign" => value.signum(),
+                    // a byte of the program assembled so far
+                    _ => (value as usize)
+                        .checked_sub(self.origin)
+                        .and_then(|pos| self.rom.get(pos))
+                        .copied()
+                        .unwrap_or(0) as f64,
+                }
+            }
+            "HERE" => self.here as f64,
+            "PI" => consts::PI,
+            "E" => consts::E,
+            name => match self.known(name) {
+                Some(value) => value,
+                None => return self.error(format!("{} isn't known yet", name)),
+            },
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GAME_ENTRY;
+
+    #[test]
+    fn test_comparisons() {
+        let rom = assemble(
+            ": main
+               if v1 > v2 then v0 := 1
+               if v1 < 5 begin
+                 v0 := 2
+               end
+               if v3 key then v0 += -1",
+            GAME_ENTRY,
+        )
+        .unwrap();
+        assert_eq!(
+            rom,
+            vec![
+                0x12, 0x02, // jump main
+                0x8f, 0x20, 0x8f, 0x15, 0x3f, 0x01, 0x60, 0x01, // vf := v2, vf -= v1
+                0x6f, 0x05, 0x8f, 0x17, 0x4f, 0x01, 0x12, 0x14, 0x60,
+                0x02, // vf := 5, vf =- v1
+                0xe3, 0xa1, 0x70, 0xff,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_data() {
+        let rom = assemble(
+            ": main
+               i := long data
+               :unpack 0xA data
+               :next target
+               v0 := 0
+               :byte { 3 * 2 + 1 } # right to left, so 9
+               jump main
+             : data
+               1 2 -1
+               jump target",
+            GAME_ENTRY,
+        )
+        .unwrap();
+        assert_eq!(
+            rom,
+            vec![
+                0x12, 0x02, 0xf0, 0x00, 0x02, 0x0f, 0x60, 0xa2, 0x61, 0x0f, 0x60, 0x00, 0x09, 0x12,
+                0x02, 0x01, 0x02, 0xff, 0x12, 0x0b,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source| assemble(source, GAME_ENTRY).unwrap_err();
+        assert_eq!(error(": main\n  v0 := 1\n  jump nowhere\n").line, 3);
+        assert_eq!(error(": main\n  v0 := 300").line, 2);
+        assert_eq!(error("v0 := 1").message, "the label main is missing");
+        assert_eq!(
+            error(": main\n loop\n  v0 := 1").message,
+            "loop without again"
+        );
+        assert_eq!(
+            error(": main : main").message,
+            "the label main is defined twice"
+        );
+        assert_eq!(
+            error(":macro m { m }\n: main\n  m").message,
+            "macro m expands too often"
+        );
+    }
+
+    #[test]
+    fn test_origin() {
+        let rom = assemble(": main\n  i := data\n  jump main\n: data 1", 0x600).unwrap();
+        assert_eq!(rom, vec![0x16, 0x02, 0xa6, 0x06, 0x16, 0x02, 0x01]);
+        assert!(assemble(":org 0x200\n: main", 0x600).is_err());
+    }
+}
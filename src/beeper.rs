@@ -0,0 +1,59 @@
+// tone of the buzzer, the original hardware had no pitch control
+const FREQUENCY: u32 = 440;
+const VOLUME: i16 = 4000;
+
+// Square wave, which sounds while the sound timer runs
+pub struct Beeper {
+    sample_rate: u32,
+    phase: u32, // samples into the current period
+}
+
+impl Beeper {
+    pub fn new(sample_rate: u32) -> Self {
+        Beeper {
+            sample_rate,
+            phase: 0,
+        }
+    }
+
+    // fill interleaved stereo samples, silence if the beeper is off
+    pub fn fill(&mut self, on: bool, samples: &mut [i16]) {
+        if !on {
+            // start the next beep at the beginning of a period
+            self.phase = 0;
+            samples.fill(0);
+            return;
+        }
+        let period = (self.sample_rate / FREQUENCY).max(2);
+        for frame in samples.chunks_mut(2) {
+            let level = if self.phase < period / 2 {
+                VOLUME
+            } else {
+                -VOLUME
+            };
+            frame.fill(level);
+            self.phase = (self.phase + 1) % period;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_square_wave() {
+        let mut beeper = Beeper::new(44100);
+        let mut samples = [0; 200];
+        beeper.fill(true, &mut samples);
+        // 100 samples per period, both channels alike
+        assert_eq!(samples[0], VOLUME);
+        assert_eq!(samples[1], VOLUME);
+        assert_eq!(samples[98], VOLUME);
+        assert_eq!(samples[100], -VOLUME);
+        assert_eq!(samples[199], -VOLUME);
+
+        beeper.fill(false, &mut samples);
+        assert!(samples.iter().all(|&sample| sample == 0));
+    }
+}
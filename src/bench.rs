@@ -0,0 +1,106 @@
+use std::time::{Duration, Instant};
+
+use crate::engine::Engine;
+use crate::processor::Processor;
+
+// cycles between timer ticks, as in a frame at a high tickrate
+const CYCLES_PER_TICK: usize = 1000;
+
+// A small rom looping forever, stressing one part of the processor
+pub struct Workload {
+    pub name: &'static str,
+    pub rom: &'static [u8],
+}
+
+pub const WORKLOADS: [Workload; 3] = [
+    Workload {
+        name: "alu",
+        rom: &[
+            0x60, 0x00, // V0 = 0
+            0x61, 0x01, // V1 = 1
+            0x80, 0x14, // V0 += V1
+            0x81, 0x05, // V1 -= V0
+            0x82, 0x06, // V2 = V0 >> 1
+            0x73, 0x05, // V3 += 5
+            0x82, 0x33, // V2 ^= V3
+            0xa3, 0x00, // I = 0x300
+            0xf3, 0x1e, // I += V3
+            0x12, 0x04, // loop
+        ],
+    },
+    Workload {
+        name: "draw",
+        rom: &[
+            0x62, 0x00, // V2 = 0, the digit
+            0x60, 0x00, // V0 = 0, x
+            0x61, 0x00, // V1 = 0, y
+            0xf2, 0x29, // I = sprite of V2
+            0xd0, 0x15, // draw it
+            0x70, 0x05, // x += 5
+            0x72, 0x01, // next digit...
+            0x64, 0x0f, // V4 = 0x0f
+            0x82, 0x42, // ...up to F
+            0x71, 0x06, // y += 6
+            0x12, 0x06, // loop
+        ],
+    },
+    Workload {
+        name: "memory",
+        rom: &[
+            0xa3, 0x00, // I = 0x300
+            0x70, 0x01, // V0 += 1
+            0xf0, 0x33, // BCD of V0
+            0xf2, 0x65, // load V0 to V2
+            0x22, 0x10, // call 0x210
+            0x31, 0x00, // skip if V1 == 0
+            0x71, 0x01, // V1 += 1
+            0x12, 0x00, // loop
+            0xa3, 0x10, // 0x210: I = 0x310
+            0xf3, 0x55, // store V0 to V3
+            0x00, 0xee, // return
+        ],
+    },
+];
+
+pub fn workload(name: &str) -> Option<&'static Workload> {
+    WORKLOADS.iter().find(|workload| workload.name == name)
+}
+
+// run a number of instructions without any frontend
+pub fn run(rom: &[u8], engine: Engine, instructions: usize) {
+    let mut processor = Processor::new();
+    processor.set_engine(engine);
+    processor.load_game(rom);
+
+    let mut left = instructions;
+    while left > 0 {
+        let cycles = left.min(CYCLES_PER_TICK);
+        processor.run(cycles);
+        processor.tick_timers();
+        left -= cycles;
+    }
+}
+
+// million instructions per second
+pub fn mips(rom: &[u8], engine: Engine, instructions: usize) -> f64 {
+    let start = Instant::now();
+    run(rom, engine, instructions);
+    let elapsed = start.elapsed().max(Duration::from_nanos(1));
+    instructions as f64 / elapsed.as_secs_f64() / 1_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workloads_run() {
+        for workload in &WORKLOADS {
+            for &engine in &[Engine::Interpreter, Engine::Cached, Engine::Jit] {
+                assert!(mips(workload.rom, engine, 10_000) > 0.0);
+            }
+        }
+        assert!(workload("draw").is_some());
+        assert!(workload("sound").is_none());
+    }
+}
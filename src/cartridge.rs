@@ -1,28 +1,251 @@
 use std::fs::File;
-use std::io::prelude::*;
+use std::io::{self, prelude::*, Cursor};
+use std::path::Path;
 
-const BUFFER_SIZE: usize = 3584;
+use sha1::{Digest, Sha1};
+use zip::ZipArchive;
+
+use crate::assembler;
+use crate::database::RomInfo;
+use crate::octo::{self, OctoError};
+
+// every zip file starts with a local file header
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+// fields are only read by the Debug output of main
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum CartridgeError {
+    Io(io::Error),
+    TooLarge { size: usize, max: usize },
+    Zip(zip::result::ZipError),
+    EmptyArchive,
+    EntryNotFound(String),
+    MultipleEntries(Vec<String>), // pick one of these
+    Octo(OctoError),
+}
 
 pub struct Cartridge {
-    pub rom: [u8; BUFFER_SIZE],
-    pub size: usize,
+    pub rom: Vec<u8>,
+    pub info: Option<RomInfo>, // settings, which came along with the rom
+    source: Option<String>,    // octo source the rom was assembled from
 }
 
 impl Cartridge {
-    pub fn new(filename: &str) -> Self {
-        let mut file = File::open(filename).expect("Error while opening file!");
-        let mut buffer = [0u8; BUFFER_SIZE];
+    // filename "-" reads from stdin, entry picks a rom out of zip archives
+    pub fn new(filename: &str, entry: Option<&str>) -> Result<Self, CartridgeError> {
+        let mut buffer = Vec::new();
 
-        // either read a byte, or noting (0)
-        let bytes = if let Ok(bytes) = file.read(&mut buffer) {
-            bytes
+        // read everything, a single read might stop early
+        if filename == "-" {
+            io::stdin().read_to_end(&mut buffer)
         } else {
-            0
-        };
+            File::open(filename).and_then(|mut file| file.read_to_end(&mut buffer))
+        }
+        .map_err(CartridgeError::Io)?;
+
+        Cartridge::from_data(buffer, filename, entry)
+    }
 
-        Cartridge {
+    // a rom already in memory, name is where it came from
+    pub fn from_data(
+        mut buffer: Vec<u8>,
+        name: &str,
+        entry: Option<&str>,
+    ) -> Result<Self, CartridgeError> {
+        let mut name = name.to_string();
+        if buffer.starts_with(ZIP_MAGIC) {
+            let (entry, content) = unzip(&buffer, entry)?;
+            name = entry;
+            buffer = content;
+        }
+
+        // octo cartridges carry their program and its settings inside an image
+        let mut info = None;
+        let mut source = None;
+        if octo::GIF_MAGIC
+            .iter()
+            .any(|magic| buffer.starts_with(magic))
+        {
+            let title = Path::new(&name)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or(name);
+            let cartridge =
+                octo::decode(&buffer, &title, crate::GAME_ENTRY).map_err(CartridgeError::Octo)?;
+            buffer = cartridge.rom;
+            info = Some(cartridge.info);
+            source = Some(cartridge.source);
+        }
+
+        Ok(Cartridge {
             rom: buffer,
-            size: bytes,
+            info,
+            source,
+        })
+    }
+
+    // assemble octo cartridges again for another load address, other roms stay as they are
+    pub fn relocate(&mut self, origin: usize) -> Result<(), CartridgeError> {
+        if let Some(source) = &self.source {
+            self.rom = assembler::assemble(source, origin)
+                .map_err(|err| CartridgeError::Octo(OctoError::Source(err)))?;
+        }
+        Ok(())
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.rom
+    }
+
+    // error if the rom doesn't fit into max bytes of memory
+    pub fn validate(&self, max: usize) -> Result<(), CartridgeError> {
+        if self.rom.len() > max {
+            return Err(CartridgeError::TooLarge {
+                size: self.rom.len(),
+                max,
+            });
+        }
+        Ok(())
+    }
+
+    // hex encoded sha1 hash of the rom, to identify it
+    pub fn sha1(&self) -> String {
+        Sha1::digest(self.data())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+// name and content of a single file in the archive
+fn unzip(archive: &[u8], entry: Option<&str>) -> Result<(String, Vec<u8>), CartridgeError> {
+    let mut archive = ZipArchive::new(Cursor::new(archive)).map_err(CartridgeError::Zip)?;
+
+    let names: Vec<String> = archive
+        .file_names()
+        .filter(|name| !name.ends_with('/'))
+        .map(String::from)
+        .collect();
+
+    let name = match (entry, names.as_slice()) {
+        (Some(entry), _) => entry.to_string(),
+        (None, [name]) => name.clone(),
+        (None, []) => return Err(CartridgeError::EmptyArchive),
+        (None, _) => {
+            let mut names = names;
+            names.sort();
+            return Err(CartridgeError::MultipleEntries(names));
         }
+    };
+
+    let mut file = archive.by_name(&name).map_err(|error| match error {
+        zip::result::ZipError::FileNotFound => CartridgeError::EntryNotFound(name.clone()),
+        error => CartridgeError::Zip(error),
+    })?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer).map_err(CartridgeError::Io)?;
+    Ok((name, buffer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use zip::write::{FileOptions, ZipWriter};
+
+    fn write_rom(name: &str, rom: &[u8]) -> String {
+        let path = env::temp_dir().join(name);
+        fs::write(&path, rom).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_read_whole_file() {
+        let filename = write_rom("chip8-rs-whole.ch8", &[0xab; 5000]);
+        let cartridge = Cartridge::new(&filename, None).unwrap();
+        assert_eq!(cartridge.data().len(), 5000);
+    }
+
+    #[test]
+    fn test_validate() {
+        let filename = write_rom("chip8-rs-validate.ch8", &[0; 3585]);
+        let cartridge = Cartridge::new(&filename, None).unwrap();
+        assert!(cartridge.validate(4096).is_ok());
+        match cartridge.validate(3584) {
+            Err(CartridgeError::TooLarge { size, max }) => assert_eq!((size, max), (3585, 3584)),
+            _ => panic!("rom should be too large"),
+        }
+    }
+
+    #[test]
+    fn test_missing_file() {
+        assert!(matches!(
+            Cartridge::new("/nonexistent/rom.ch8", None),
+            Err(CartridgeError::Io(_))
+        ));
+    }
+
+    fn write_zip(name: &str, files: &[(&str, &[u8])]) -> String {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        let archive = zip.finish().unwrap().into_inner();
+        write_rom(name, &archive)
+    }
+
+    #[test]
+    fn test_zip() {
+        let filename = write_zip("chip8-rs-single.zip", &[("pong.ch8", &[1, 2, 3])]);
+        let cartridge = Cartridge::new(&filename, None).unwrap();
+        assert_eq!(cartridge.data(), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_zip_entries() {
+        let filename = write_zip(
+            "chip8-rs-multiple.zip",
+            &[("tetris.ch8", &[4, 5]), ("pong.ch8", &[1, 2, 3])],
+        );
+        match Cartridge::new(&filename, None) {
+            Err(CartridgeError::MultipleEntries(names)) => {
+                assert_eq!(names, vec!["pong.ch8", "tetris.ch8"])
+            }
+            _ => panic!("entry should be ambiguous"),
+        }
+
+        let cartridge = Cartridge::new(&filename, Some("tetris.ch8")).unwrap();
+        assert_eq!(cartridge.data(), &[4, 5]);
+
+        assert!(matches!(
+            Cartridge::new(&filename, Some("brix.ch8")),
+            Err(CartridgeError::EntryNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_sha1() {
+        let filename = write_rom("chip8-rs-sha1.ch8", b"abc");
+        let cartridge = Cartridge::new(&filename, None).unwrap();
+        assert_eq!(cartridge.sha1(), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    }
+
+    #[test]
+    fn test_octo() {
+        let gif = octo::tests::encode(
+            r#"{ "program": ": main\n  jump main", "options": { "tickrate": 30 } }"#,
+        );
+        let filename = write_rom("chip8-rs-loop.gif", &gif);
+        let mut cartridge = Cartridge::new(&filename, None).unwrap();
+        assert_eq!(cartridge.data(), &[0x12, 0x02, 0x12, 0x02]);
+        cartridge.relocate(0x600).unwrap();
+        assert_eq!(cartridge.data(), &[0x16, 0x02, 0x16, 0x02]);
+
+        let info = cartridge.info.unwrap();
+        assert_eq!(info.title, "chip8-rs-loop");
+        assert_eq!(info.tickrate, Some(30));
     }
 }
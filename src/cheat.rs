@@ -0,0 +1,250 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::processor::Processor;
+
+pub const NAMES: [&str; 5] = ["equal", "changed", "unchanged", "increased", "decreased"];
+
+#[derive(Debug)]
+pub enum CheatError {
+    Io(io::Error),
+    InvalidLine(usize), // counting from 1
+}
+
+// How a byte has to relate to its value at the last search step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl Comparison {
+    // value is only used by equal
+    pub fn from_name(name: &str, value: u8) -> Option<Self> {
+        match name {
+            "equal" => Some(Comparison::Equal(value)),
+            "changed" => Some(Comparison::Changed),
+            "unchanged" => Some(Comparison::Unchanged),
+            "increased" => Some(Comparison::Increased),
+            "decreased" => Some(Comparison::Decreased),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, before: u8, now: u8) -> bool {
+        match *self {
+            Comparison::Equal(value) => now == value,
+            Comparison::Changed => now != before,
+            Comparison::Unchanged => now == before,
+            Comparison::Increased => now > before,
+            Comparison::Decreased => now < before,
+        }
+    }
+}
+
+// Narrows down where a game keeps a value: start with all of memory, play a bit,
+// then keep the addresses whose value e.g. decreased along with the lives
+pub struct Search {
+    snapshot: Vec<u8>,
+    candidates: Vec<usize>,
+}
+
+impl Search {
+    pub fn new(memory: &[u8]) -> Self {
+        Search {
+            snapshot: memory.to_vec(),
+            candidates: (0..memory.len()).collect(),
+        }
+    }
+
+    // drop the candidates not matching, and take a new snapshot to compare the next step with
+    pub fn filter(&mut self, memory: &[u8], comparison: Comparison) -> usize {
+        let snapshot = &self.snapshot;
+        self.candidates
+            .retain(|&address| match memory.get(address) {
+                Some(&now) => comparison.matches(snapshot[address], now),
+                None => false,
+            });
+        self.snapshot = memory.to_vec();
+        self.candidates.len()
+    }
+
+    pub fn candidates(&self) -> &[usize] {
+        &self.candidates
+    }
+}
+
+// A byte kept at a fixed value, written again after every frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cheat {
+    pub address: usize,
+    pub value: u8,
+}
+
+impl Cheat {
+    // hex address and value, e.g. "2f0:03"
+    pub fn parse(code: &str) -> Option<Self> {
+        let (address, value) = code.trim().split_once(':')?;
+        let address = usize::from_str_radix(address.trim(), 16).ok()?;
+        let value = u8::from_str_radix(value.trim(), 16).ok()?;
+        Some(Cheat { address, value })
+    }
+}
+
+// The cheats of a single rom, with a description each
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CheatList {
+    cheats: Vec<(Cheat, String)>,
+}
+
+impl CheatList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // one cheat per line, code first, e.g. "2f0:03 infinite lives", # starts a comment
+    pub fn parse(text: &str) -> Result<Self, CheatError> {
+        let mut list = CheatList::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (code, name) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let cheat = Cheat::parse(code).ok_or(CheatError::InvalidLine(number + 1))?;
+            list.add(cheat, name.trim());
+        }
+        Ok(list)
+    }
+
+    // the list in <directory>/<sha1 of the rom>.cht, empty without such a file
+    pub fn load(directory: &str, sha1: &str) -> Result<Self, CheatError> {
+        let path = Path::new(directory).join(format!("{}.cht", sha1.to_ascii_lowercase()));
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(CheatList::new()),
+            Err(err) => Err(CheatError::Io(err)),
+        }
+    }
+
+    // replaces a cheat at the same address
+    pub fn add(&mut self, cheat: Cheat, name: &str) {
+        self.remove(cheat.address);
+        self.cheats.push((cheat, name.to_string()));
+    }
+
+    pub fn remove(&mut self, address: usize) {
+        self.cheats.retain(|(cheat, _)| cheat.address != address);
+    }
+
+    pub fn clear(&mut self) {
+        self.cheats.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cheats.is_empty()
+    }
+
+    pub fn cheats(&self) -> impl Iterator<Item = (&Cheat, &str)> {
+        self.cheats
+            .iter()
+            .map(|(cheat, name)| (cheat, name.as_str()))
+    }
+
+    // write the frozen values, addresses outside of memory are left out
+    pub fn apply(&self, processor: &mut Processor) {
+        for (cheat, _) in self.cheats.iter() {
+            let current = processor.memory().get(cheat.address).copied();
+            if current.is_some_and(|value| value != cheat.value) {
+                processor.write_memory(cheat.address, cheat.value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let mut memory = vec![3, 3, 5, 0];
+        let mut search = Search::new(&memory);
+        assert_eq!(search.filter(&memory, Comparison::Equal(3)), 2);
+
+        // lives at 1 go down
+        memory[1] = 2;
+        memory[2] = 6;
+        assert_eq!(search.filter(&memory, Comparison::Decreased), 1);
+        assert_eq!(search.candidates(), &[1]);
+        assert_eq!(search.filter(&memory, Comparison::Unchanged), 1);
+        assert_eq!(search.filter(&memory, Comparison::Changed), 0);
+    }
+
+    #[test]
+    fn test_parse() {
+        let list = CheatList::parse("# pong\n2F0:03 infinite lives\n\n 300:ff\n").unwrap();
+        let cheats: Vec<_> = list.cheats().collect();
+        assert_eq!(
+            cheats,
+            vec![
+                (
+                    &Cheat {
+                        address: 0x2f0,
+                        value: 3
+                    },
+                    "infinite lives"
+                ),
+                (
+                    &Cheat {
+                        address: 0x300,
+                        value: 0xff
+                    },
+                    ""
+                ),
+            ]
+        );
+        assert!(matches!(
+            CheatList::parse("2f0:03\n2f0:300"),
+            Err(CheatError::InvalidLine(2))
+        ));
+        assert_eq!(
+            CheatList::load("/nonexistent", "abc").unwrap(),
+            CheatList::new()
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut processor = Processor::new();
+        let mut list = CheatList::new();
+        list.add(
+            Cheat {
+                address: 0x300,
+                value: 1,
+            },
+            "",
+        );
+        list.add(
+            Cheat {
+                address: 0x300,
+                value: 9,
+            },
+            "replaced",
+        );
+        list.add(
+            Cheat {
+                address: 0x10000,
+                value: 1,
+            },
+            "outside",
+        );
+        list.apply(&mut processor);
+        assert_eq!(processor.memory()[0x300], 9);
+        assert_eq!(list.cheats().count(), 2);
+    }
+}
@@ -0,0 +1,70 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+// Defaults for options missing on the command line, e.g.
+// { "palette": "amber", "foreground": "#ffd780" }
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub palette: Option<String>,
+    pub colors: Option<Vec<String>>,
+    pub foreground: Option<String>,
+    pub background: Option<String>,
+}
+
+impl Config {
+    pub fn from_file(filename: &str) -> Result<Self, ConfigError> {
+        let json = fs::read_to_string(filename).map_err(ConfigError::Io)?;
+        Config::parse(&json)
+    }
+
+    fn parse(json: &str) -> Result<Self, ConfigError> {
+        serde_json::from_str(json).map_err(ConfigError::Json)
+    }
+
+    // the file in the user's config directory, nothing set without one
+    pub fn load_default() -> Result<Self, ConfigError> {
+        match default_path() {
+            Some(path) if path.exists() => Config::from_file(&path.to_string_lossy()),
+            _ => Ok(Config::default()),
+        }
+    }
+}
+
+// $XDG_CONFIG_HOME/chip8-rs/config.json, or ~/.config/chip8-rs/config.json
+pub fn default_path() -> Option<PathBuf> {
+    let directory = match env::var_os("XDG_CONFIG_HOME") {
+        Some(directory) if !directory.is_empty() => PathBuf::from(directory),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(directory.join("chip8-rs").join("config.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config =
+            Config::parse(r##"{ "palette": "amber", "colors": ["#000000", "#ffffff"] }"##).unwrap();
+        assert_eq!(config.palette.as_deref(), Some("amber"));
+        assert_eq!(config.colors.unwrap().len(), 2);
+        assert_eq!(config.foreground, None);
+
+        assert!(matches!(
+            Config::parse(r#"{ "pallete": "amber" }"#),
+            Err(ConfigError::Json(_))
+        ));
+    }
+}
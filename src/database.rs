@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+use serde::Deserialize;
+
+use crate::input::Controls;
+use crate::palette;
+use crate::platform::{Platform, Quirks};
+
+// Same layout as programs.json of the community chip-8-database
+// (https://github.com/chip-8/chip-8-database), so it can be swapped in.
+const BUNDLED: &str = include_str!("../database/programs.json");
+
+// fields are only read by the Debug output of main
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum DatabaseError {
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+// Everything known about a single rom
+#[derive(Debug, Clone, PartialEq)]
+pub struct RomInfo {
+    pub title: String,
+    pub platform: Option<Platform>,
+    pub quirks: Option<Quirks>,
+    pub tickrate: Option<usize>,
+    pub controls: Controls,
+    pub colors: Vec<u32>,
+}
+
+pub struct Database {
+    programs: Vec<Program>,
+}
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    roms: HashMap<String, Rom>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, QuirkOverrides>,
+    tickrate: Option<usize>,
+    #[serde(default)]
+    keys: HashMap<String, usize>,
+    colors: Option<Colors>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QuirkOverrides {
+    shift: Option<bool>,
+    memory_leave_i_unchanged: Option<bool>,
+    wrap: Option<bool>,
+    jump: Option<bool>,
+    vblank: Option<bool>,
+    logic: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+impl Database {
+    pub fn bundled() -> Self {
+        Database::parse(BUNDLED).expect("Bundled database is broken!")
+    }
+
+    pub fn from_file(filename: &str) -> Result<Self, DatabaseError> {
+        let json = fs::read_to_string(filename).map_err(DatabaseError::Io)?;
+        Database::parse(&json)
+    }
+
+    fn parse(json: &str) -> Result<Self, DatabaseError> {
+        let programs = serde_json::from_str(json).map_err(DatabaseError::Json)?;
+        Ok(Database { programs })
+    }
+
+    // find a rom by the sha1 hash of its content
+    pub fn lookup(&self, sha1: &str) -> Option<RomInfo> {
+        let sha1 = sha1.to_ascii_lowercase();
+        self.programs.iter().find_map(|program| {
+            program
+                .roms
+                .get(&sha1)
+                .map(|rom| rom_info(&program.title, rom))
+        })
+    }
+}
+
+fn rom_info(title: &str, rom: &Rom) -> RomInfo {
+    // first platform we know of, database platform names are more fine grained
+    let platform = rom.platforms.iter().find_map(|name| {
+        let platform = match name.as_str() {
+            "originalChip8" | "hybridVIP" | "modernChip8" => Platform::Chip8,
+            "chip48" | "superchip1" | "superchip" => Platform::Schip,
+            "xochip" => Platform::XoChip,
+            _ => return None,
+        };
+        Some((name, platform))
+    });
+
+    // the platform's quirks, with the ones the rom needs differently
+    let quirks = platform.map(|(name, platform)| {
+        let mut quirks = platform.quirks();
+        if let Some(overrides) = rom.quirky_platforms.get(name) {
+            let flags = [
+                (overrides.logic, &mut quirks.vf_reset),
+                (overrides.shift, &mut quirks.shift_in_place),
+                (overrides.jump, &mut quirks.jump_vx),
+                (overrides.vblank, &mut quirks.display_wait),
+            ];
+            for (value, quirk) in flags {
+                if let Some(value) = value {
+                    *quirk = value;
+                }
+            }
+            if let Some(unchanged) = overrides.memory_leave_i_unchanged {
+                quirks.increment_index = !unchanged;
+            }
+            if let Some(wrap) = overrides.wrap {
+                quirks.clip_sprites = !wrap;
+            }
+        }
+        quirks
+    });
+
+    let key = |name: &str| rom.keys.get(name).copied().filter(|&key| key < 16);
+
+    RomInfo {
+        title: title.to_string(),
+        platform: platform.map(|(_, platform)| platform),
+        quirks,
+        tickrate: rom.tickrate,
+        controls: Controls {
+            up: key("up"),
+            down: key("down"),
+            left: key("left"),
+            right: key("right"),
+            a: key("a"),
+            b: key("b"),
+        },
+        colors: rom
+            .colors
+            .iter()
+            .flat_map(|colors| colors.pixels.iter())
+            .filter_map(|color| palette::parse_color(color))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha1::{Digest, Sha1};
+
+    const PROGRAMS: &str = r##"[
+        {
+            "title": "Test Game",
+            "roms": {
+                "0123456789abcdef0123456789abcdef01234567": {
+                    "file": "test.ch8",
+                    "platforms": ["originalChip8"],
+                    "quirkyPlatforms": {
+                        "originalChip8": { "vblank": false, "wrap": true }
+                    },
+                    "tickrate": 15,
+                    "keys": { "up": 5, "a": 6 },
+                    "colors": { "pixels": ["#000000", "#ff0000"] }
+                }
+            }
+        }
+    ]"##;
+
+    #[test]
+    fn test_bundled() {
+        // the IBM logo, which draws the letters and loops forever
+        let rom = [
+            0x00, 0xe0, 0xa2, 0x2a, 0x60, 0x0c, 0x61, 0x08, 0xd0, 0x1f, 0x70, 0x09, 0xa2, 0x39,
+            0xd0, 0x1f, 0xa2, 0x48, 0x70, 0x08, 0xd0, 0x1f, 0x70, 0x04, 0xa2, 0x57, 0xd0, 0x1f,
+            0x70, 0x08, 0xa2, 0x66, 0xd0, 0x1f, 0x70, 0x08, 0xa2, 0x75, 0xd0, 0x1f, 0x12, 0x28,
+            0xff, 0x00, 0xff, 0x00, 0x3c, 0x00, 0x3c, 0x00, 0x3c, 0x00, 0x3c, 0x00, 0xff, 0x00,
+            0xff, 0xff, 0x00, 0xff, 0x00, 0x38, 0x00, 0x3f, 0x00, 0x3f, 0x00, 0x38, 0x00, 0xff,
+            0x00, 0xff, 0x80, 0x00, 0xe0, 0x00, 0xe0, 0x00, 0x80, 0x00, 0x80, 0x00, 0xe0, 0x00,
+            0xe0, 0x00, 0x80, 0xf8, 0x00, 0xfc, 0x00, 0x3e, 0x00, 0x3f, 0x00, 0x3b, 0x00, 0x39,
+            0x00, 0xf8, 0x00, 0xf8, 0x03, 0x00, 0x07, 0x00, 0x0f, 0x00, 0xbf, 0x00, 0xfb, 0x00,
+            0xf3, 0x00, 0xe3, 0x00, 0x43, 0xe0, 0x00, 0xe0, 0x00, 0x80, 0x00, 0x80, 0x00, 0x80,
+            0x00, 0x80, 0x00, 0xe0, 0x00, 0xe0,
+        ];
+        let sha1: String = Sha1::digest(rom)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        let info = Database::bundled().lookup(&sha1).unwrap();
+        assert_eq!(info.title, "IBM Logo");
+        assert_eq!(info.platform, Some(Platform::Chip8));
+        assert_eq!(info.quirks, Some(Platform::Chip8.quirks()));
+    }
+
+    #[test]
+    fn test_lookup() {
+        let database = Database::parse(PROGRAMS).unwrap();
+        assert_eq!(
+            database.lookup("ffffffffffffffffffffffffffffffffffffffff"),
+            None
+        );
+
+        let info = database
+            .lookup("0123456789ABCDEF0123456789ABCDEF01234567")
+            .unwrap();
+        assert_eq!(info.title, "Test Game");
+        assert_eq!(info.platform, Some(Platform::Chip8));
+        assert_eq!(info.tickrate, Some(15));
+        assert_eq!(info.controls.up, Some(5));
+        assert_eq!(info.controls.a, Some(6));
+        assert_eq!(info.controls.down, None);
+        assert_eq!(info.colors, vec![0x000000, 0xff0000]);
+
+        let quirks = info.quirks.unwrap();
+        assert!(quirks.vf_reset);
+        assert!(!quirks.display_wait);
+        assert!(!quirks.clip_sprites);
+    }
+}
@@ -1,49 +1,249 @@
-use sdl2;
-use sdl2::{pixels, rect::Rect, render::Canvas, video::Window};
+use sdl2::pixels::{self, PixelFormatEnum};
+use sdl2::{rect::Rect, render::Canvas, render::Texture, video::FullscreenType, video::Window};
+
+use crate::filter::{Filter, Phosphor};
+use crate::palette::Palette;
+
+pub struct DisplayOptions {
+    pub title: String,
+    pub palette: Palette,
+    pub scale: usize,
+    pub integer_scale: bool, // only scale by whole numbers
+    pub fullscreen: bool,
+    pub filter: Filter, // anti flicker filter
+}
 
 pub struct Display {
     canvas: Canvas<Window>,
+    texture: Texture, // streaming texture holding one frame
+    frame: Vec<u8>,   // 4 bytes per pixel, as in the texture
+    colors: Vec<u32>, // filtered colour of each pixel
+    phosphor: Phosphor,
+    palette: Palette,
+    scale: usize,
+    integer_scale: bool,
+    status: Option<String>, // shown in the top right corner
 }
 
 impl Display {
-    pub fn new(sdl_ctx: &sdl2::Sdl) -> Self {
+    pub fn new(sdl_ctx: &sdl2::Sdl, options: DisplayOptions) -> Self {
         let video = sdl_ctx.video().unwrap();
-        let window = video
+        let mut window = video
             .window(
-                "chip8-rs",
-                (crate::SCREEN_WIDTH * crate::SCREEN_SCALE) as u32,
-                (crate::SCREEN_HEIGHT * crate::SCREEN_SCALE) as u32,
+                &options.title,
+                (crate::SCREEN_WIDTH * options.scale) as u32,
+                (crate::SCREEN_HEIGHT * options.scale) as u32,
             )
             .position_centered()
+            .resizable()
             .opengl()
             .build()
             .unwrap();
 
+        if options.fullscreen {
+            let _ = window.set_fullscreen(FullscreenType::Desktop);
+        }
+
         let mut canvas = window.into_canvas().build().unwrap();
+        let texture = canvas
+            .texture_creator()
+            .create_texture_streaming(
+                PixelFormatEnum::RGB888,
+                crate::SCREEN_WIDTH as u32,
+                crate::SCREEN_HEIGHT as u32,
+            )
+            .unwrap();
 
-        canvas.set_draw_color(pixels::Color::RGB(0, 0, 0));
+        canvas.set_draw_color(to_color(options.palette.color(0)));
         canvas.clear();
         canvas.present();
 
-        Display { canvas }
+        Display {
+            canvas,
+            texture,
+            frame: vec![0; crate::SCREEN_WIDTH * crate::SCREEN_HEIGHT * 4],
+            colors: vec![0; crate::SCREEN_WIDTH * crate::SCREEN_HEIGHT],
+            phosphor: Phosphor::new(options.filter),
+            palette: options.palette,
+            scale: options.scale,
+            integer_scale: options.integer_scale,
+            status: None,
+        }
     }
 
     pub fn draw(&mut self, pixel: &[[u8; crate::SCREEN_WIDTH]; crate::SCREEN_HEIGHT]) {
-        for (y, &row) in pixel.iter().enumerate() {
-            for (x, &column) in row.iter().enumerate() {
-                self.canvas.set_draw_color(if column == 1 {
-                    pixels::Color::RGB(255, 255, 255)
-                } else {
-                    pixels::Color::RGB(0, 0, 0)
-                });
-                let _ = self.canvas.fill_rect(Rect::new(
-                    (x * crate::SCREEN_SCALE) as i32,
-                    (y * crate::SCREEN_SCALE) as i32,
-                    crate::SCREEN_SCALE as u32,
-                    crate::SCREEN_SCALE as u32,
-                ));
-            }
+        // clear letterbox bars
+        self.canvas.set_draw_color(pixels::Color::RGB(0, 0, 0));
+        self.canvas.clear();
+
+        // convert the screen to colours...
+        self.phosphor.apply(pixel, &self.palette, &mut self.colors);
+        for (bytes, color) in self.frame.chunks_exact_mut(4).zip(&self.colors) {
+            bytes.copy_from_slice(&color.to_ne_bytes());
         }
+
+        // ...upload them in one go and let the renderer scale them
+        let _ = self
+            .texture
+            .update(None, &self.frame, crate::SCREEN_WIDTH * 4);
+
+        let (width, height) = self.canvas.output_size().unwrap_or((0, 0));
+        let view = viewport(width, height, self.integer_scale);
+        let _ = self.canvas.copy(&self.texture, None, view);
+        self.draw_status(view);
         self.canvas.present();
     }
+
+    pub fn set_status(&mut self, status: Option<String>) {
+        self.status = status;
+    }
+
+    // status text with a tiny font, on a box in the top right corner of the screen
+    fn draw_status(&mut self, view: Rect) {
+        let status = match &self.status {
+            Some(status) => status,
+            None => return,
+        };
+        let unit = (view.width() / crate::SCREEN_WIDTH as u32 / 2).max(1);
+        let width = (status.chars().count() as u32 * 4 + 1) * unit;
+        let left = view.right() - width as i32 - unit as i32;
+        let top = view.top() + unit as i32;
+
+        let mut dots = Vec::new();
+        for (pos, c) in status.chars().enumerate() {
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) != 0 {
+                        dots.push(Rect::new(
+                            left + ((pos * 4 + column + 1) as u32 * unit) as i32,
+                            top + ((row + 1) as u32 * unit) as i32,
+                            unit,
+                            unit,
+                        ));
+                    }
+                }
+            }
+        }
+
+        self.canvas.set_draw_color(to_color(self.palette.color(0)));
+        let _ = self.canvas.fill_rect(Rect::new(left, top, width, 7 * unit));
+        self.canvas.set_draw_color(to_color(self.palette.color(1)));
+        let _ = self.canvas.fill_rects(&dots);
+    }
+
+    // whether draw has to be called every frame, even without screen updates
+    pub fn is_animated(&self) -> bool {
+        self.phosphor.is_active()
+    }
+
+    pub fn toggle_fullscreen(&mut self) {
+        let window = self.canvas.window_mut();
+        let fullscreen = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+        let _ = window.set_fullscreen(fullscreen);
+    }
+
+    // resize the window to a multiple of the chip8 resolution
+    pub fn change_scale(&mut self, delta: isize) {
+        let scale = (self.scale as isize + delta).max(1) as usize;
+        let window = self.canvas.window_mut();
+        if window.fullscreen_state() != FullscreenType::Off {
+            return;
+        }
+        let _ = window.set_size(
+            (crate::SCREEN_WIDTH * scale) as u32,
+            (crate::SCREEN_HEIGHT * scale) as u32,
+        );
+        self.scale = scale;
+    }
+}
+
+// biggest area with the chip8 aspect ratio, centered in the window
+fn viewport(width: u32, height: u32, integer_scale: bool) -> Rect {
+    let screen_width = crate::SCREEN_WIDTH as f32;
+    let screen_height = crate::SCREEN_HEIGHT as f32;
+
+    let mut scale = (width as f32 / screen_width).min(height as f32 / screen_height);
+    if integer_scale && scale >= 1.0 {
+        scale = scale.floor();
+    }
+
+    let view_width = ((screen_width * scale) as u32).max(1);
+    let view_height = ((screen_height * scale) as u32).max(1);
+    Rect::new(
+        (width.saturating_sub(view_width) / 2) as i32,
+        (height.saturating_sub(view_height) / 2) as i32,
+        view_width,
+        view_height,
+    )
+}
+
+// 3x5 dots of the characters of status texts and script overlays, one row per byte
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        _ => [0; 5],
+    }
+}
+
+fn to_color(rgb: u32) -> pixels::Color {
+    pixels::Color::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_viewport() {
+        // exact fit
+        assert_eq!(viewport(1280, 640, false), Rect::new(0, 0, 1280, 640));
+        // letterbox top and bottom
+        assert_eq!(viewport(1280, 1000, false), Rect::new(0, 180, 1280, 640));
+        // pillarbox left and right
+        assert_eq!(viewport(1000, 320, false), Rect::new(180, 0, 640, 320));
+        // snap to whole numbers
+        assert_eq!(viewport(1000, 1000, true), Rect::new(20, 260, 960, 480));
+    }
 }
@@ -0,0 +1,20 @@
+// How the processor runs instructions, all give the same results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    Interpreter, // fetch and decode every instruction, the reference
+    Cached,      // decode every address once, until memory gets written
+    Jit,         // compile blocks of arithmetic to x86-64 code, cached otherwise
+}
+
+pub const NAMES: [&str; 3] = ["interpreter", "cached", "jit"];
+
+impl Engine {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "interpreter" => Some(Engine::Interpreter),
+            "cached" => Some(Engine::Cached),
+            "jit" => Some(Engine::Jit),
+            _ => None,
+        }
+    }
+}
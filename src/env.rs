@@ -0,0 +1,228 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::input::KeyEvent;
+use crate::processor::Processor;
+use crate::state::StateError;
+
+// keys 0 to F, and pressing nothing
+pub const ACTIONS: usize = 17;
+pub const NOOP: usize = 16;
+
+// reward of a step, from the memory before and after it
+pub type Reward = Box<dyn Fn(&[u8], &[u8]) -> f64 + Send + Sync>;
+// whether an episode is over, from the memory after a step
+pub type Done = Box<dyn Fn(&[u8]) -> bool + Send + Sync>;
+
+// How a number is stored in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Byte,
+    Word,       // big endian, like the processor itself
+    Bcd(usize), // one byte per decimal digit, as written by fx33
+}
+
+impl Encoding {
+    pub fn read(&self, memory: &[u8], address: usize) -> f64 {
+        let byte = |pos: usize| memory.get(address + pos).copied().unwrap_or(0) as f64;
+        match *self {
+            Encoding::Byte => byte(0),
+            Encoding::Word => byte(0) * 256.0 + byte(1),
+            Encoding::Bcd(digits) => (0..digits).fold(0.0, |value, pos| value * 10.0 + byte(pos)),
+        }
+    }
+}
+
+// the increase of a score kept in memory
+pub fn score(address: usize, encoding: Encoding) -> Reward {
+    Box::new(move |before, after| encoding.read(after, address) - encoding.read(before, address))
+}
+
+// over once a value in memory reaches a given one, e.g. no lives left
+pub fn value_reaches(address: usize, encoding: Encoding, value: f64) -> Done {
+    Box::new(move |memory| encoding.read(memory, address) == value)
+}
+
+// A gym like environment for agents to play a rom. Every step taps a key
+// (or none) during frame_skip frames, and observes the screen afterwards.
+pub struct Env {
+    processor: Processor,
+    rom: Vec<u8>,
+    cycles_per_frame: usize,
+    frame_skip: usize,
+    reward: Reward,
+    done: Done,
+    crashed: bool, // the rom took the processor down, the episode is over until a reset
+}
+
+impl Env {
+    // processor is set up already, with platform and engine
+    pub fn new(processor: Processor, rom: &[u8]) -> Self {
+        let mut env = Env {
+            processor,
+            rom: rom.to_vec(),
+            cycles_per_frame: crate::CYCLES_PER_FRAME,
+            frame_skip: 4,
+            reward: Box::new(|_, _| 0.0),
+            done: Box::new(|_| false),
+            crashed: false,
+        };
+        env.reset();
+        env
+    }
+
+    pub fn set_tickrate(&mut self, cycles_per_frame: usize) {
+        self.cycles_per_frame = cycles_per_frame;
+    }
+
+    // frames per step, at least one
+    pub fn set_frame_skip(&mut self, frame_skip: usize) {
+        self.frame_skip = frame_skip.max(1);
+    }
+
+    pub fn set_reward(&mut self, reward: Reward) {
+        self.reward = reward;
+    }
+
+    pub fn set_done(&mut self, done: Done) {
+        self.done = done;
+    }
+
+    pub fn processor(&self) -> &Processor {
+        &self.processor
+    }
+
+    pub fn crashed(&self) -> bool {
+        self.crashed
+    }
+
+    // start the rom over, returns the first observation
+    pub fn reset(&mut self) -> Vec<u8> {
+        self.crashed = false;
+        self.processor.reset();
+        self.processor.load_game(&self.rom);
+        self.observation()
+    }
+
+    // panics on actions outside of 0..ACTIONS, a crashing rom ends the episode without reward
+    pub fn step(&mut self, action: usize) -> (Vec<u8>, f64, bool) {
+        assert!(action < ACTIONS, "action {} out of range", action);
+        if self.crashed {
+            return (self.observation(), 0.0, true);
+        }
+        let before = self.processor.memory().to_vec();
+
+        // a tap held for the first half of the frames, as fx0a waits for the release
+        let key = if action == NOOP { None } else { Some(action) };
+        let held = self.frame_skip.div_ceil(2);
+        let processor = &mut self.processor;
+        let (frame_skip, cycles_per_frame) = (self.frame_skip, self.cycles_per_frame);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            for frame in 0..frame_skip {
+                match key {
+                    Some(key) if frame == 0 => processor.key_event(KeyEvent::Pressed(key)),
+                    Some(key) if frame == held => processor.key_event(KeyEvent::Released(key)),
+                    _ => {}
+                }
+                processor.run_frame(cycles_per_frame);
+            }
+            if let (Some(key), true) = (key, held == frame_skip) {
+                processor.key_event(KeyEvent::Released(key));
+            }
+        }));
+        if result.is_err() {
+            self.crashed = true;
+            return (self.observation(), 0.0, true);
+        }
+
+        let after = self.processor.memory();
+        let reward = (self.reward)(&before, after);
+        let done = (self.done)(after);
+        (self.observation(), reward, done)
+    }
+
+    // the screen, row by row
+    pub fn observation(&self) -> Vec<u8> {
+        self.processor.screen().iter().flatten().copied().collect()
+    }
+
+    // everything needed to come back to this point, e.g. for tree search
+    pub fn clone_state(&self) -> Vec<u8> {
+        self.processor.save_state()
+    }
+
+    pub fn restore_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        self.processor.load_state(state)?;
+        self.crashed = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // counts key 5 presses into V0, stores its bcd at 0x300 and ends at 3
+    const ROM: [u8; 14] = [
+        0xa3, 0x00, // I = 0x300
+        0xf1, 0x0a, // wait for a key into V1
+        0x41, 0x05, // skip unless key 5
+        0x70, 0x01, // V0 += 1
+        0xf0, 0x33, // bcd of V0
+        0x12, 0x02, // loop
+        0x00, 0x00,
+    ];
+
+    fn new_env() -> Env {
+        let mut env = Env::new(Processor::new(), &ROM);
+        env.set_reward(score(0x300, Encoding::Bcd(3)));
+        env.set_done(value_reaches(0x302, Encoding::Byte, 3.0));
+        env
+    }
+
+    #[test]
+    fn test_step() {
+        let mut env = new_env();
+        assert_eq!(env.step(NOOP), (env.observation(), 0.0, false));
+        assert_eq!(env.step(4).1, 0.0);
+        assert_eq!(env.step(5).1, 1.0);
+        assert_eq!(env.step(5), (env.observation(), 1.0, false));
+        assert!(env.step(5).2);
+        assert_eq!(env.observation().len(), 64 * 32);
+    }
+
+    #[test]
+    fn test_clone_state() {
+        let mut env = new_env();
+        env.step(5);
+        let state = env.clone_state();
+        env.step(5);
+        env.step(5);
+
+        env.restore_state(&state).unwrap();
+        assert_eq!(env.step(5), (env.observation(), 1.0, false));
+        env.reset();
+        assert_eq!(Encoding::Bcd(3).read(env.processor().memory(), 0x300), 0.0);
+    }
+
+    #[test]
+    fn test_crash() {
+        // i := 0xfff, then v0 and v1 are read from behind the end of memory
+        let mut env = Env::new(Processor::new(), &[0xaf, 0xff, 0xf1, 0x65]);
+        env.set_reward(Box::new(|_, _| 1.0));
+        assert_eq!(env.step(NOOP), (env.observation(), 0.0, true));
+        assert!(env.crashed());
+        assert!(env.step(NOOP).2);
+        env.reset();
+        assert!(!env.crashed());
+    }
+
+    #[test]
+    fn test_encoding() {
+        let memory = [0x01, 0x02, 0x03];
+        assert_eq!(Encoding::Byte.read(&memory, 1), 2.0);
+        assert_eq!(Encoding::Word.read(&memory, 0), 258.0);
+        assert_eq!(Encoding::Bcd(3).read(&memory, 0), 123.0);
+        // outside of memory reads as 0
+        assert_eq!(Encoding::Word.read(&memory, 2), 768.0);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::palette::Palette;
+
+// brightness kept by a pixel each frame after it was switched off
+const DECAY: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    None,
+    Or,    // show pixels lit in this or the last frame
+    Blend, // average this and the last frame
+    Decay, // switched off pixels fade out over a few frames
+}
+
+pub const NAMES: [&str; 4] = ["none", "or", "blend", "decay"];
+
+impl Filter {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Filter::None),
+            "or" => Some(Filter::Or),
+            "blend" => Some(Filter::Blend),
+            "decay" => Some(Filter::Decay),
+            _ => None,
+        }
+    }
+}
+
+// Reduces the flicker of xor drawn sprites, by remembering past frames
+pub struct Phosphor {
+    filter: Filter,
+    last: [[u8; crate::SCREEN_WIDTH]; crate::SCREEN_HEIGHT], // previous frame
+    glow: [[f32; crate::SCREEN_WIDTH]; crate::SCREEN_HEIGHT], // brightness for decay
+    glow_pixel: [[u8; crate::SCREEN_WIDTH]; crate::SCREEN_HEIGHT], // last lit value
+}
+
+impl Phosphor {
+    pub fn new(filter: Filter) -> Self {
+        Phosphor {
+            filter,
+            last: [[0; crate::SCREEN_WIDTH]; crate::SCREEN_HEIGHT],
+            glow: [[0.0; crate::SCREEN_WIDTH]; crate::SCREEN_HEIGHT],
+            glow_pixel: [[0; crate::SCREEN_WIDTH]; crate::SCREEN_HEIGHT],
+        }
+    }
+
+    // filters depending on past frames have to run every frame
+    pub fn is_active(&self) -> bool {
+        self.filter != Filter::None
+    }
+
+    // write the colour of every pixel to frame, advancing the filter by one frame
+    pub fn apply(
+        &mut self,
+        screen: &[[u8; crate::SCREEN_WIDTH]; crate::SCREEN_HEIGHT],
+        palette: &Palette,
+        frame: &mut [u32],
+    ) {
+        for (y, row) in screen.iter().enumerate() {
+            for (x, &pixel) in row.iter().enumerate() {
+                let last = self.last[y][x];
+                frame[y * crate::SCREEN_WIDTH + x] = match self.filter {
+                    Filter::None => palette.color(pixel),
+                    Filter::Or => palette.color(pixel | last),
+                    Filter::Blend => mix(palette.color(pixel), palette.color(last), 0.5),
+                    Filter::Decay => {
+                        if pixel != 0 {
+                            self.glow[y][x] = 1.0;
+                            self.glow_pixel[y][x] = pixel;
+                            palette.color(pixel)
+                        } else {
+                            self.glow[y][x] *= DECAY;
+                            if self.glow[y][x] < 0.05 {
+                                self.glow[y][x] = 0.0;
+                            }
+                            mix(
+                                palette.color(0),
+                                palette.color(self.glow_pixel[y][x]),
+                                self.glow[y][x],
+                            )
+                        }
+                    }
+                };
+            }
+        }
+        self.last = *screen;
+    }
+}
+
+// linear blend of two 0xRRGGBB colours, amount 0.0 is from, 1.0 is to
+fn mix(from: u32, to: u32, amount: f32) -> u32 {
+    let channel = |shift: u32| {
+        let from = ((from >> shift) & 0xff) as f32;
+        let to = ((to >> shift) & 0xff) as f32;
+        ((from + (to - from) * amount).round() as u32) << shift
+    };
+    channel(16) | channel(8) | channel(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen_with(pixel: u8) -> [[u8; crate::SCREEN_WIDTH]; crate::SCREEN_HEIGHT] {
+        let mut screen = [[0; crate::SCREEN_WIDTH]; crate::SCREEN_HEIGHT];
+        screen[0][0] = pixel;
+        screen
+    }
+
+    #[test]
+    fn test_mix() {
+        assert_eq!(mix(0x000000, 0xffffff, 0.0), 0x000000);
+        assert_eq!(mix(0x000000, 0xffffff, 1.0), 0xffffff);
+        assert_eq!(mix(0x000000, 0x804020, 0.5), 0x402010);
+    }
+
+    #[test]
+    fn test_or() {
+        let palette = Palette::default();
+        let mut phosphor = Phosphor::new(Filter::Or);
+        let mut frame = [0; crate::SCREEN_WIDTH * crate::SCREEN_HEIGHT];
+
+        phosphor.apply(&screen_with(1), &palette, &mut frame);
+        assert_eq!(frame[0], 0xffffff);
+        // still visible one frame after switching off...
+        phosphor.apply(&screen_with(0), &palette, &mut frame);
+        assert_eq!(frame[0], 0xffffff);
+        // ...but not two
+        phosphor.apply(&screen_with(0), &palette, &mut frame);
+        assert_eq!(frame[0], 0x000000);
+    }
+
+    #[test]
+    fn test_decay() {
+        let palette = Palette::default();
+        let mut phosphor = Phosphor::new(Filter::Decay);
+        let mut frame = [0; crate::SCREEN_WIDTH * crate::SCREEN_HEIGHT];
+
+        phosphor.apply(&screen_with(1), &palette, &mut frame);
+        assert_eq!(frame[0], 0xffffff);
+        phosphor.apply(&screen_with(0), &palette, &mut frame);
+        assert_eq!(frame[0], 0x808080);
+        phosphor.apply(&screen_with(0), &palette, &mut frame);
+        assert_eq!(frame[0], 0x404040);
+        // fully gone after a few frames
+        for _ in 0..4 {
+            phosphor.apply(&screen_with(0), &palette, &mut frame);
+        }
+        assert_eq!(frame[0], 0x000000);
+    }
+}
@@ -0,0 +1,81 @@
+#[cfg(feature = "sdl")]
+use crate::display::{Display, DisplayOptions};
+use crate::input::InputEvent;
+#[cfg(feature = "sdl")]
+use crate::input::{Controls, Input};
+use crate::processor::Processor;
+
+// Something to show the screen on and read the keypad from
+pub trait Frontend {
+    // all input since the last frame, error if the user wants to quit
+    #[allow(clippy::result_unit_err)]
+    fn fetch(&mut self) -> Result<Vec<InputEvent>, ()>;
+
+    fn draw(&mut self, processor: &Processor);
+
+    // whether draw has to be called every frame, even without screen updates
+    fn is_animated(&self) -> bool;
+
+    // show the speed or pause state, nothing at normal speed
+    fn set_status(&mut self, status: Option<String>);
+}
+
+#[cfg(feature = "sdl")]
+pub struct SdlFrontend {
+    _sdl_ctx: sdl2::Sdl,
+    display: Display,
+    input: Input,
+}
+
+#[cfg(feature = "sdl")]
+impl SdlFrontend {
+    pub fn new(options: DisplayOptions, controls: Controls) -> Self {
+        let sdl_ctx = sdl2::init().unwrap();
+        let display = Display::new(&sdl_ctx, options);
+        let input = Input::new(&sdl_ctx, controls);
+
+        SdlFrontend {
+            _sdl_ctx: sdl_ctx,
+            display,
+            input,
+        }
+    }
+}
+
+#[cfg(feature = "sdl")]
+impl Frontend for SdlFrontend {
+    fn fetch(&mut self) -> Result<Vec<InputEvent>, ()> {
+        let mut input_events = self.input.fetch()?;
+
+        // window controls are handled right here
+        let display = &mut self.display;
+        input_events.retain(|input_event| match input_event {
+            InputEvent::ToggleFullscreen => {
+                display.toggle_fullscreen();
+                false
+            }
+            InputEvent::ScaleUp => {
+                display.change_scale(1);
+                false
+            }
+            InputEvent::ScaleDown => {
+                display.change_scale(-1);
+                false
+            }
+            _ => true,
+        });
+        Ok(input_events)
+    }
+
+    fn draw(&mut self, processor: &Processor) {
+        self.display.draw(processor.screen());
+    }
+
+    fn is_animated(&self) -> bool {
+        self.display.is_animated()
+    }
+
+    fn set_status(&mut self, status: Option<String>) {
+        self.display.set_status(status);
+    }
+}
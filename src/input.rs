@@ -1,62 +1,142 @@
-use sdl2;
-use sdl2::{event::Event, keyboard::Keycode};
+#[cfg(feature = "sdl")]
+use sdl2::{event::Event, event::WindowEvent, keyboard::Keycode};
 
+// A change of a single key on the hex keypad
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEvent {
+    Pressed(usize),
+    Released(usize),
+}
+
+// Everything the emulator reacts to, besides closing the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    Key(KeyEvent),
+    ToggleFullscreen,
+    ScaleUp,
+    ScaleDown,
+    Redraw, // window got resized or uncovered
+    TogglePause,
+    FrameAdvance, // run a single frame and pause
+    SpeedUp,
+    SpeedDown,
+    ToggleFastForward, // as fast as possible
+    Reset,             // restart the game
+}
+
+// Keypad keys a game uses for directions and actions, to put on the
+// arrow keys, space (a) and return (b) in addition to the usual layout
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Controls {
+    pub up: Option<usize>,
+    pub down: Option<usize>,
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+    pub a: Option<usize>,
+    pub b: Option<usize>,
+}
+
+#[cfg(feature = "sdl")]
 pub struct Input {
     events: sdl2::EventPump,
+    controls: Controls,
 }
 
+#[cfg(feature = "sdl")]
 impl Input {
-    pub fn new(sdl_ctx: &sdl2::Sdl) -> Self {
+    pub fn new(sdl_ctx: &sdl2::Sdl, controls: Controls) -> Self {
         Input {
             events: sdl_ctx.event_pump().unwrap(),
+            controls,
         }
     }
 
-    pub fn fetch(&mut self) -> Result<[bool; 16], ()> {
-        // error if we don't get input
+    #[allow(clippy::result_unit_err)]
+    pub fn fetch(&mut self) -> Result<Vec<InputEvent>, ()> {
+        let mut input_events = Vec::new();
+        let controls = &self.controls;
+
         for event in self.events.poll_iter() {
-            if let Event::Quit { .. } = event {
-                return Err(());
+            match event {
+                // error if the window got closed
+                Event::Quit { .. } => return Err(()),
+                // ignore key repeat, we only care about edges
+                Event::KeyDown {
+                    keycode: Some(key),
+                    repeat: false,
+                    ..
+                } => {
+                    if let Some(index) = keymap(key, controls) {
+                        input_events.push(InputEvent::Key(KeyEvent::Pressed(index)));
+                    } else if let Some(hotkey) = hotkey(key) {
+                        input_events.push(hotkey);
+                    }
+                }
+                Event::KeyUp {
+                    keycode: Some(key), ..
+                } => {
+                    if let Some(index) = keymap(key, controls) {
+                        input_events.push(InputEvent::Key(KeyEvent::Released(index)));
+                    }
+                }
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..),
+                    ..
+                }
+                | Event::Window {
+                    win_event: WindowEvent::Exposed,
+                    ..
+                } => input_events.push(InputEvent::Redraw),
+                _ => {}
             }
         }
+        Ok(input_events)
+    }
+}
 
-        // get all inputs
-        let input: Vec<Keycode> = self
-            .events
-            .keyboard_state()
-            .pressed_scancodes()
-            .filter_map(Keycode::from_scancode)
-            .collect();
-
-        // set all no not-pressed as default
-        let mut keys = [false; 16];
-
-        // map to internal values
-        for key in input {
-            let index = match key {
-                Keycode::Num1 => Some(0x1),
-                Keycode::Num2 => Some(0x2),
-                Keycode::Num3 => Some(0x3),
-                Keycode::Num4 => Some(0xc),
-                Keycode::Q => Some(0x4),
-                Keycode::W => Some(0x5),
-                Keycode::E => Some(0x6),
-                Keycode::R => Some(0xd),
-                Keycode::A => Some(0x7),
-                Keycode::S => Some(0x8),
-                Keycode::D => Some(0x9),
-                Keycode::F => Some(0xe),
-                Keycode::Z => Some(0xa),
-                Keycode::X => Some(0x0),
-                Keycode::C => Some(0xb),
-                Keycode::V => Some(0xf),
-                _ => None,
-            };
-
-            if let Some(pos) = index {
-                keys[pos] = true;
-            }
-        }
-        Ok(keys)
+// map to internal values
+#[cfg(feature = "sdl")]
+fn keymap(key: Keycode, controls: &Controls) -> Option<usize> {
+    match key {
+        Keycode::Up => controls.up,
+        Keycode::Down => controls.down,
+        Keycode::Left => controls.left,
+        Keycode::Right => controls.right,
+        Keycode::Space => controls.a,
+        Keycode::Return => controls.b,
+        Keycode::Num1 => Some(0x1),
+        Keycode::Num2 => Some(0x2),
+        Keycode::Num3 => Some(0x3),
+        Keycode::Num4 => Some(0xc),
+        Keycode::Q => Some(0x4),
+        Keycode::W => Some(0x5),
+        Keycode::E => Some(0x6),
+        Keycode::R => Some(0xd),
+        Keycode::A => Some(0x7),
+        Keycode::S => Some(0x8),
+        Keycode::D => Some(0x9),
+        Keycode::F => Some(0xe),
+        Keycode::Z => Some(0xa),
+        Keycode::X => Some(0x0),
+        Keycode::C => Some(0xb),
+        Keycode::V => Some(0xf),
+        _ => None,
+    }
+}
+
+// keys outside of the keypad, which control the emulator itself
+#[cfg(feature = "sdl")]
+fn hotkey(key: Keycode) -> Option<InputEvent> {
+    match key {
+        Keycode::F11 => Some(InputEvent::ToggleFullscreen),
+        Keycode::Equals => Some(InputEvent::ScaleUp),
+        Keycode::Minus => Some(InputEvent::ScaleDown),
+        Keycode::P | Keycode::Pause => Some(InputEvent::TogglePause),
+        Keycode::N => Some(InputEvent::FrameAdvance),
+        Keycode::RightBracket => Some(InputEvent::SpeedUp),
+        Keycode::LeftBracket => Some(InputEvent::SpeedDown),
+        Keycode::Tab => Some(InputEvent::ToggleFastForward),
+        Keycode::F5 => Some(InputEvent::Reset),
+        _ => None,
     }
 }
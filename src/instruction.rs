@@ -0,0 +1,126 @@
+// The instructions of an opcode, shared by the processor and the analysis, so
+// both agree on what an opcode means. Operands are x, y, n, kk and nnn of Cowgod's reference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Clear,                              // 00e0
+    Return,                             // 00ee
+    Jump(usize),                        // 1nnn
+    Call(usize),                        // 2nnn
+    SkipEqual(usize, u8),               // 3xkk
+    SkipNotEqual(usize, u8),            // 4xkk
+    SkipEqualRegister(usize, usize),    // 5xy0
+    Load(usize, u8),                    // 6xkk
+    Add(usize, u8),                     // 7xkk
+    Move(usize, usize),                 // 8xy0
+    Or(usize, usize),                   // 8xy1
+    And(usize, usize),                  // 8xy2
+    Xor(usize, usize),                  // 8xy3
+    AddRegister(usize, usize),          // 8xy4
+    Sub(usize, usize),                  // 8xy5
+    ShiftRight(usize, usize),           // 8xy6
+    SubNegated(usize, usize),           // 8xy7
+    ShiftLeft(usize, usize),            // 8xye
+    SkipNotEqualRegister(usize, usize), // 9xy0
+    LoadIndex(usize),                   // annn
+    JumpOffset(usize),                  // bnnn
+    Random(usize, u8),                  // cxkk
+    Draw(usize, usize, usize),          // dxyn
+    SkipPressed(usize),                 // ex9e
+    SkipNotPressed(usize),              // exa1
+    LoadDelay(usize),                   // fx07
+    WaitKey(usize),                     // fx0a
+    SetDelay(usize),                    // fx15
+    SetSound(usize),                    // fx18
+    AddIndex(usize),                    // fx1e
+    Font(usize),                        // fx29
+    BigFont(usize),                     // fx30
+    Bcd(usize),                         // fx33
+    Store(usize),                       // fx55
+    Restore(usize),                     // fx65
+    Unknown,                            // the processor steps over it
+}
+
+impl Instruction {
+    pub fn decode(opcode: u16) -> Self {
+        // values from http://devernay.free.fr/hacks/chip8/C8TECH10.HTM#3.0
+
+        let nibbles = (
+            ((opcode & 0xF000) >> 12) as usize,
+            ((opcode & 0x0F00) >> 8) as usize,
+            ((opcode & 0x00F0) >> 4) as usize,
+            (opcode & 0x000F) as usize,
+        );
+
+        // x - A 4-bit value, the lower 4 bits of the high byte of the instruction
+        let x = nibbles.1;
+        // y - A 4-bit value, the upper 4 bits of the low byte of the instruction
+        let y = nibbles.2;
+        // n or nibble - A 4-bit value, the lowest 4 bits of the instruction
+        let n = nibbles.3;
+        // kk or byte - An 8-bit value, the lowest 8 bits of the instruction
+        let kk = (opcode & 0x00FF) as u8;
+        // nnn or addr - A 12-bit value, the lowest 12 bits of the instruction
+        let nnn = (opcode & 0x0FFF) as usize;
+
+        // match nibbles to opcodes
+        match nibbles {
+            (0x00, 0x00, 0x0e, 0x00) => Instruction::Clear,
+            (0x00, 0x00, 0x0e, 0x0e) => Instruction::Return,
+            (0x01, _, _, _) => Instruction::Jump(nnn),
+            (0x02, _, _, _) => Instruction::Call(nnn),
+            (0x03, _, _, _) => Instruction::SkipEqual(x, kk),
+            (0x04, _, _, _) => Instruction::SkipNotEqual(x, kk),
+            (0x05, _, _, 0x00) => Instruction::SkipEqualRegister(x, y),
+            (0x06, _, _, _) => Instruction::Load(x, kk),
+            (0x07, _, _, _) => Instruction::Add(x, kk),
+            (0x08, _, _, 0x00) => Instruction::Move(x, y),
+            (0x08, _, _, 0x01) => Instruction::Or(x, y),
+            (0x08, _, _, 0x02) => Instruction::And(x, y),
+            (0x08, _, _, 0x03) => Instruction::Xor(x, y),
+            (0x08, _, _, 0x04) => Instruction::AddRegister(x, y),
+            (0x08, _, _, 0x05) => Instruction::Sub(x, y),
+            (0x08, _, _, 0x06) => Instruction::ShiftRight(x, y),
+            (0x08, _, _, 0x07) => Instruction::SubNegated(x, y),
+            (0x08, _, _, 0x0e) => Instruction::ShiftLeft(x, y),
+            (0x09, _, _, 0x00) => Instruction::SkipNotEqualRegister(x, y),
+            (0x0a, _, _, _) => Instruction::LoadIndex(nnn),
+            (0x0b, _, _, _) => Instruction::JumpOffset(nnn),
+            (0x0c, _, _, _) => Instruction::Random(x, kk),
+            (0x0d, _, _, _) => Instruction::Draw(x, y, n),
+            (0x0e, _, 0x09, 0x0e) => Instruction::SkipPressed(x),
+            (0x0e, _, 0x0a, 0x01) => Instruction::SkipNotPressed(x),
+            (0x0f, _, 0x00, 0x07) => Instruction::LoadDelay(x),
+            (0x0f, _, 0x00, 0x0a) => Instruction::WaitKey(x),
+            (0x0f, _, 0x01, 0x05) => Instruction::SetDelay(x),
+            (0x0f, _, 0x01, 0x08) => Instruction::SetSound(x),
+            (0x0f, _, 0x01, 0x0e) => Instruction::AddIndex(x),
+            (0x0f, _, 0x02, 0x09) => Instruction::Font(x),
+            (0x0f, _, 0x03, 0x00) => Instruction::BigFont(x),
+            (0x0f, _, 0x03, 0x03) => Instruction::Bcd(x),
+            (0x0f, _, 0x05, 0x05) => Instruction::Store(x),
+            (0x0f, _, 0x06, 0x05) => Instruction::Restore(x),
+            _ => Instruction::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(Instruction::decode(0x00e0), Instruction::Clear);
+        assert_eq!(Instruction::decode(0x2468), Instruction::Call(0x468));
+        assert_eq!(
+            Instruction::decode(0x8ab4),
+            Instruction::AddRegister(0xa, 0xb)
+        );
+        assert_eq!(Instruction::decode(0xd125), Instruction::Draw(1, 2, 5));
+        assert_eq!(Instruction::decode(0xf333), Instruction::Bcd(3));
+        // no such variant of 5xy0, 8xyn or fxkk
+        assert_eq!(Instruction::decode(0x5121), Instruction::Unknown);
+        assert_eq!(Instruction::decode(0x812f), Instruction::Unknown);
+        assert_eq!(Instruction::decode(0xf0ff), Instruction::Unknown);
+    }
+}
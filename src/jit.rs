@@ -0,0 +1,375 @@
+// Translates runs of register-only instructions into x86-64 machine code.
+// Each block is a function taking a pointer to V0..VF in rdi and to I in rsi,
+// everything else (jumps, skips, dxyn, memory access, ...) stays with the
+// interpreter. The generated code mirrors the interpreter step by step,
+// including the order in which VF and Vx get written.
+
+use crate::platform::Quirks;
+
+// instructions per block, so a block fits into the cycles left of a frame
+const MAX_BLOCK: usize = 32;
+// shorter blocks run faster in the interpreter, than calling native code
+const MIN_BLOCK: usize = 3;
+const CODE_SIZE: usize = 1 << 20;
+
+const AL: u8 = 0;
+const CL: u8 = 1;
+const DL: u8 = 2;
+const VF: u8 = 0x0f;
+
+#[derive(Clone, Copy)]
+struct Block {
+    offset: usize, // start in the code buffer
+    len: usize,    // number of chip8 instructions
+}
+
+#[derive(Clone, Copy)]
+enum Slot {
+    Unknown,
+    Interpreted, // the instruction at this address can't be compiled
+    Compiled(Block),
+}
+
+pub struct Jit {
+    code: CodeBuffer,
+    slots: Vec<Slot>,    // block starting at each address
+    compiled: Vec<bool>, // address is part of a compiled block
+    quirks: Quirks,
+}
+
+impl Jit {
+    // None on platforms without a code generator
+    pub fn new(quirks: Quirks) -> Option<Self> {
+        Some(Jit {
+            code: CodeBuffer::new()?,
+            slots: Vec::new(),
+            compiled: Vec::new(),
+            quirks,
+        })
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+        self.clear();
+    }
+
+    // forget all compiled code, e.g. after loading a new game
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.compiled.clear();
+        self.code.clear();
+    }
+
+    // forget blocks containing address, after the program wrote to it
+    pub fn invalidate(&mut self, address: usize) {
+        // most writes go to data, so only look further for code
+        // (interpreted addresses can stay so, they are always right)
+        if !self.compiled.get(address).copied().unwrap_or(false) {
+            return;
+        }
+        let first = address.saturating_sub(MAX_BLOCK * 2 - 1);
+        let last = (address + 1).min(self.slots.len());
+        for start in first..last {
+            if let Slot::Compiled(block) = self.slots[start] {
+                if address < start + block.len * 2 {
+                    self.slots[start] = Slot::Unknown;
+                }
+            }
+        }
+    }
+
+    // run the block at pc, if it takes at most budget instructions,
+    // returns the number of instructions run
+    #[inline]
+    pub fn run(
+        &mut self,
+        memory: &[u8],
+        pc: usize,
+        registers: &mut [u8; 16],
+        index: &mut usize,
+        budget: usize,
+    ) -> Option<usize> {
+        // emptied whenever all code got forgotten
+        if self.slots.len() != memory.len() {
+            self.slots = vec![Slot::Unknown; memory.len()];
+            self.compiled = vec![false; memory.len()];
+        }
+        let block = match self.slots[pc] {
+            Slot::Compiled(block) => block,
+            Slot::Interpreted => return None,
+            Slot::Unknown => match self.compile(memory, pc) {
+                Some(block) => {
+                    self.slots[pc] = Slot::Compiled(block);
+                    self.compiled[pc..pc + block.len * 2].fill(true);
+                    block
+                }
+                None => {
+                    self.slots[pc] = Slot::Interpreted;
+                    return None;
+                }
+            },
+        };
+        if block.len > budget {
+            return None;
+        }
+        unsafe { self.code.call(block.offset, registers.as_mut_ptr(), index) };
+        Some(block.len)
+    }
+
+    fn compile(&mut self, memory: &[u8], pc: usize) -> Option<Block> {
+        let mut code = Vec::new();
+        let mut len = 0;
+        let mut address = pc;
+        while len < MAX_BLOCK && address + 1 < memory.len() {
+            let opcode = (memory[address] as u16) << 8 | memory[address + 1] as u16;
+            if !emit(opcode, &self.quirks, &mut code) {
+                break;
+            }
+            len += 1;
+            address += 2;
+        }
+        if len < MIN_BLOCK {
+            return None;
+        }
+        code.push(0xc3); // ret
+
+        let offset = match self.code.append(&code) {
+            Some(offset) => offset,
+            None => {
+                // out of space, start over
+                self.slots.fill(Slot::Unknown);
+                self.compiled.fill(false);
+                self.code.clear();
+                self.code.append(&code)?
+            }
+        };
+        Some(Block { offset, len })
+    }
+}
+
+// machine code for a single instruction, false if it isn't supported
+fn emit(opcode: u16, quirks: &Quirks, code: &mut Vec<u8>) -> bool {
+    let x = ((opcode & 0x0F00) >> 8) as u8;
+    let y = ((opcode & 0x00F0) >> 4) as u8;
+    let kk = (opcode & 0x00FF) as u8;
+    let nnn = (opcode & 0x0FFF) as u32;
+    let source = if quirks.shift_in_place { x } else { y };
+
+    match (opcode >> 12, opcode & 0x000F) {
+        // mov byte [rdi+x], kk
+        (0x6, _) => code.extend([0xc6, 0x47, x, kk]),
+        // add byte [rdi+x], kk
+        (0x7, _) => code.extend([0x80, 0x47, x, kk]),
+        (0x8, 0x0) => {
+            load(code, AL, y);
+            store(code, x, AL);
+        }
+        (0x8, op @ 0x1..=0x3) => {
+            load(code, AL, x);
+            // or / and / xor al, [rdi+y]
+            let instruction = [0x0a, 0x22, 0x32][op as usize - 1];
+            code.extend([instruction, 0x47, y]);
+            store(code, x, AL);
+            if quirks.vf_reset {
+                code.extend([0xc6, 0x47, VF, 0x00]);
+            }
+        }
+        (0x8, 0x4) => {
+            load(code, AL, x);
+            code.extend([0x02, 0x47, y]); // add al, [rdi+y]
+            code.extend([0x0f, 0x92, 0xc1]); // setc cl
+            store(code, x, AL);
+            store(code, VF, CL);
+        }
+        (0x8, 0x5) => {
+            load(code, AL, x);
+            load(code, DL, y);
+            code.extend([0x38, 0xd0]); // cmp al, dl
+            code.extend([0x0f, 0x97, 0xc1]); // seta cl
+            store(code, VF, CL);
+            code.extend([0x28, 0xd0]); // sub al, dl
+            store(code, x, AL);
+        }
+        (0x8, 0x6) => {
+            load(code, AL, source);
+            code.extend([0x24, 0x01]); // and al, 1
+            store(code, VF, AL);
+            load(code, AL, source);
+            code.extend([0xd0, 0xe8]); // shr al, 1
+            store(code, x, AL);
+        }
+        (0x8, 0x7) => {
+            load(code, AL, y);
+            code.extend([0x3a, 0x47, x]); // cmp al, [rdi+x]
+            code.extend([0x0f, 0x97, 0xc1]); // seta cl
+            store(code, VF, CL);
+            load(code, AL, y);
+            code.extend([0x2a, 0x47, x]); // sub al, [rdi+x]
+            store(code, x, AL);
+        }
+        (0x8, 0xe) => {
+            load(code, AL, source);
+            code.extend([0xc0, 0xe8, 0x07]); // shr al, 7
+            store(code, VF, AL);
+            load(code, AL, source);
+            code.extend([0xd0, 0xe0]); // shl al, 1
+            store(code, x, AL);
+        }
+        // mov qword [rsi], nnn
+        (0xa, _) => {
+            code.extend([0x48, 0xc7, 0x06]);
+            code.extend(nnn.to_le_bytes());
+        }
+        (0xf, _) if kk == 0x1e => {
+            code.extend([0x0f, 0xb6, 0x47, x]); // movzx eax, byte [rdi+x]
+            code.extend([0x48, 0x01, 0x06]); // add [rsi], rax
+        }
+        _ => return false,
+    }
+    true
+}
+
+// mov reg, [rdi+register]
+fn load(code: &mut Vec<u8>, reg: u8, register: u8) {
+    code.extend([0x8a, 0x47 | reg << 3, register]);
+}
+
+// mov [rdi+register], reg
+fn store(code: &mut Vec<u8>, register: u8, reg: u8) {
+    code.extend([0x88, 0x47 | reg << 3, register]);
+}
+
+// Memory, which is either writable or executable
+#[cfg(all(unix, target_arch = "x86_64"))]
+struct CodeBuffer {
+    memory: *mut u8,
+    used: usize,
+}
+
+#[cfg(all(unix, target_arch = "x86_64"))]
+impl CodeBuffer {
+    fn new() -> Option<Self> {
+        let memory = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                CODE_SIZE,
+                libc::PROT_READ | libc::PROT_EXEC,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if memory == libc::MAP_FAILED {
+            return None;
+        }
+        Some(CodeBuffer {
+            memory: memory as *mut u8,
+            used: 0,
+        })
+    }
+
+    fn clear(&mut self) {
+        self.used = 0;
+    }
+
+    // copy code behind the existing one, returns where it starts
+    fn append(&mut self, code: &[u8]) -> Option<usize> {
+        if self.used + code.len() > CODE_SIZE {
+            return None;
+        }
+        let offset = self.used;
+        unsafe {
+            self.protect(libc::PROT_READ | libc::PROT_WRITE)?;
+            std::ptr::copy_nonoverlapping(code.as_ptr(), self.memory.add(offset), code.len());
+            self.protect(libc::PROT_READ | libc::PROT_EXEC)?;
+        }
+        self.used += code.len();
+        Some(offset)
+    }
+
+    unsafe fn protect(&self, protection: libc::c_int) -> Option<()> {
+        match libc::mprotect(self.memory as *mut libc::c_void, CODE_SIZE, protection) {
+            0 => Some(()),
+            _ => None,
+        }
+    }
+
+    // offset has to be the start of a block returned by append
+    unsafe fn call(&self, offset: usize, registers: *mut u8, index: *mut usize) {
+        let block: extern "sysv64" fn(*mut u8, *mut usize) =
+            std::mem::transmute(self.memory.add(offset));
+        block(registers, index);
+    }
+}
+
+// the mapping belongs to the buffer alone, so it can move between threads
+#[cfg(all(unix, target_arch = "x86_64"))]
+unsafe impl Send for CodeBuffer {}
+
+#[cfg(all(unix, target_arch = "x86_64"))]
+impl Drop for CodeBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.memory as *mut libc::c_void, CODE_SIZE);
+        }
+    }
+}
+
+// no code generator for other platforms, Jit::new fails
+#[cfg(not(all(unix, target_arch = "x86_64")))]
+struct CodeBuffer;
+
+#[cfg(not(all(unix, target_arch = "x86_64")))]
+impl CodeBuffer {
+    fn new() -> Option<Self> {
+        None
+    }
+
+    fn clear(&mut self) {}
+
+    fn append(&mut self, _code: &[u8]) -> Option<usize> {
+        None
+    }
+
+    unsafe fn call(&self, _offset: usize, _registers: *mut u8, _index: *mut usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emit() {
+        let quirks = Quirks::default();
+        let mut code = Vec::new();
+        assert!(emit(0x6a42, &quirks, &mut code));
+        assert_eq!(code, vec![0xc6, 0x47, 0x0a, 0x42]);
+
+        // left to the interpreter
+        for opcode in [0x1200, 0x3a00, 0xd015, 0xf055, 0xc0ff] {
+            assert!(!emit(opcode, &quirks, &mut code));
+        }
+    }
+
+    #[test]
+    fn test_block_ends_at_unsupported() {
+        let mut jit = match Jit::new(Quirks::default()) {
+            Some(jit) => jit,
+            None => return,
+        };
+        let memory = [0x60, 0x05, 0x70, 0x01, 0xa3, 0x00, 0x12, 0x00];
+        let mut registers = [0; 16];
+        let mut index = 0;
+
+        // doesn't fit into the budget
+        assert_eq!(jit.run(&memory, 0, &mut registers, &mut index, 2), None);
+        assert_eq!(registers[0], 0);
+
+        assert_eq!(jit.run(&memory, 0, &mut registers, &mut index, 10), Some(3));
+        assert_eq!(registers[0], 6);
+        assert_eq!(index, 0x300);
+
+        // jumps are left to the interpreter
+        assert_eq!(jit.run(&memory, 6, &mut registers, &mut index, 10), None);
+    }
+}
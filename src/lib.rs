@@ -0,0 +1,36 @@
+pub mod analysis;
+pub mod assembler;
+pub mod beeper;
+pub mod bench;
+pub mod cartridge;
+pub mod cheat;
+pub mod config;
+pub mod database;
+#[cfg(feature = "sdl")]
+pub mod display;
+pub mod engine;
+pub mod env;
+pub mod filter;
+pub mod fontset;
+pub mod frontend;
+pub mod input;
+pub mod instruction;
+pub mod jit;
+pub mod octo;
+pub mod palette;
+pub mod platform;
+pub mod processor;
+pub mod remote;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod speed;
+pub mod state;
+pub mod terminal;
+pub mod watch;
+
+pub const MEMORY_SIZE: usize = 4096;
+pub const GAME_ENTRY: usize = 0x200; // most games load into 0x200
+pub const SCREEN_WIDTH: usize = 64;
+pub const SCREEN_HEIGHT: usize = 32;
+pub const FRAME_RATE: u32 = 60; // timers and screen run at 60Hz
+pub const CYCLES_PER_FRAME: usize = 10; // ~600 instructions per second
@@ -1,23 +1,62 @@
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
 
-mod cartridge;
-mod display;
-mod fontset;
-mod input;
-mod processor;
+use chip8_rs::analysis::{Analysis, Region};
+use chip8_rs::bench::{self, WORKLOADS};
+use chip8_rs::cartridge::{Cartridge, CartridgeError};
+use chip8_rs::cheat::{CheatError, CheatList};
+use chip8_rs::config::{Config, ConfigError};
+use chip8_rs::database::{Database, DatabaseError};
+use chip8_rs::display::DisplayOptions;
+use chip8_rs::engine::Engine;
+use chip8_rs::filter::Filter;
+use chip8_rs::fontset::{Font, BIG_FONT, BIG_FONT_SIZE, FONT_SIZE};
+use chip8_rs::frontend::{Frontend, SdlFrontend};
+use chip8_rs::palette::Palette;
+use chip8_rs::platform::Platform;
+use chip8_rs::processor::{Hooks, Processor};
+use chip8_rs::remote::Server;
+#[cfg(feature = "scripting")]
+use chip8_rs::script::{Script, ScriptError};
+use chip8_rs::terminal::Terminal;
+use chip8_rs::watch::Watcher;
+use chip8_rs::{engine, filter, fontset, palette, platform};
+use chip8_rs::{CYCLES_PER_FRAME, GAME_ENTRY, MEMORY_SIZE};
 
-use crate::cartridge::Cartridge;
-use crate::processor::Processor;
-
-const MEMORY_SIZE: usize = 4096;
-const GAME_ENTRY: usize = 0x200; // most games load into 0x200
-const SCREEN_WIDTH: usize = 64;
-const SCREEN_HEIGHT: usize = 32;
 const SCREEN_SCALE: usize = 20;
 
+// fields are only read by the Debug output of main
+#[allow(dead_code)]
 #[derive(Debug)]
 enum ChipError {
     CartridgeNotFound,
+    Cartridge(CartridgeError),
+    NoEntryChosen,
+    InvalidColor(String),
+    InvalidPalette(String), // of the config file
+    Config(ConfigError),
+    InvalidScale(String),
+    InvalidTickrate(String),
+    InvalidLoadAddress(String),
+    InvalidFontAddress(String),
+    FontOverlap(usize, usize), // font and load address
+    Font(std::io::Error),
+    InvalidFontSize(usize),
+    InvalidInstructions(String),
+    Terminal(std::io::Error),
+    Database(DatabaseError),
+    #[cfg(feature = "scripting")]
+    Script(ScriptError),
+    #[cfg(not(feature = "scripting"))]
+    ScriptingDisabled, // built without the scripting feature
+    Cheats(CheatError),
+    Remote(std::io::Error),
+    StdioTaken, // by the rom or the terminal, so remote calls can't use it
+    #[cfg(not(unix))]
+    SocketsUnsupported, // only stdin and stdout take remote calls
+    Output(std::io::Error),
 }
 
 fn main() -> Result<(), ChipError> {
@@ -29,18 +68,644 @@ fn main() -> Result<(), ChipError> {
             Arg::with_name("cartridge")
                 .short("c")
                 .takes_value(true)
-                .help("A cartridge binary file"),
+                .help("A cartridge binary file, an Octo cartridge gif, a zip archive of roms or - for stdin"),
+        )
+        .arg(
+            Arg::with_name("entry")
+                .short("e")
+                .long("entry")
+                .takes_value(true)
+                .help("The rom to pick out of a zip archive"),
+        )
+        .arg(
+            Arg::with_name("watch")
+                .short("w")
+                .long("watch")
+                .help("Reload and restart the rom, whenever its file changes"),
+        )
+        .arg(
+            Arg::with_name("script")
+                .long("script")
+                .takes_value(true)
+                .help("A Rhai script with hooks on frames, addresses, memory writes and keys"),
+        )
+        .arg(
+            Arg::with_name("cheats")
+                .long("cheats")
+                .takes_value(true)
+                .default_value("cheats")
+                .help("Directory of cheat lists, named after the SHA-1 of the rom with a .cht extension"),
+        )
+        .arg(
+            Arg::with_name("remote")
+                .long("remote")
+                .takes_value(true)
+                .help("A unix socket to take JSON-RPC calls on, or - for stdin and stdout (the only choice off unix)"),
+        )
+        .arg(
+            Arg::with_name("platform")
+                .long("platform")
+                .takes_value(true)
+                .possible_values(&platform::NAMES)
+                .help("Platform to emulate the quirks of, instead of looking the rom up"),
+        )
+        .arg(
+            Arg::with_name("tickrate")
+                .long("tickrate")
+                .takes_value(true)
+                .help("Instructions per frame [default: 10]"),
+        )
+        .arg(
+            Arg::with_name("load-address")
+                .long("load-address")
+                .takes_value(true)
+                .help("Address to load the rom at, e.g. 0x600 [default: 0x200, depends on the platform]"),
+        )
+        .arg(
+            Arg::with_name("font")
+                .long("font")
+                .takes_value(true)
+                .possible_values(&fontset::NAMES)
+                .help("Built-in font of an interpreter [default: classic, depends on the platform]"),
+        )
+        .arg(
+            Arg::with_name("font-file")
+                .long("font-file")
+                .takes_value(true)
+                .help("Custom font, 80 bytes of 4x5 sprites, optionally followed by 160 bytes of 8x10 sprites"),
+        )
+        .arg(
+            Arg::with_name("font-address")
+                .long("font-address")
+                .takes_value(true)
+                .help("Address to store the font at, e.g. 0x50 [default: 0]"),
+        )
+        .arg(
+            Arg::with_name("engine")
+                .long("engine")
+                .takes_value(true)
+                .possible_values(&engine::NAMES)
+                .default_value("interpreter")
+                .help("How to run instructions, cached decodes each address only once, jit compiles to x86-64"),
+        )
+        .arg(
+            Arg::with_name("database")
+                .long("database")
+                .takes_value(true)
+                .help("A programs.json of the chip-8-database, instead of the bundled one"),
+        )
+        .arg(
+            Arg::with_name("terminal")
+                .short("t")
+                .long("terminal")
+                .help("Play inside the terminal instead of a window"),
+        )
+        .arg(
+            Arg::with_name("scale")
+                .short("s")
+                .long("scale")
+                .takes_value(true)
+                .help("Window size as a multiple of 64x32 [default: 20]"),
+        )
+        .arg(
+            Arg::with_name("integer-scale")
+                .long("integer-scale")
+                .help("Only scale the screen by whole numbers"),
+        )
+        .arg(
+            Arg::with_name("fullscreen")
+                .short("f")
+                .long("fullscreen")
+                .help("Start in fullscreen, toggle with F11"),
+        )
+        .arg(
+            Arg::with_name("filter")
+                .long("filter")
+                .takes_value(true)
+                .possible_values(&filter::NAMES)
+                .default_value("none")
+                .help("Reduce flicker by keeping pixels visible over a few frames"),
+        )
+        .arg(
+            Arg::with_name("palette")
+                .short("p")
+                .long("palette")
+                .takes_value(true)
+                .possible_values(&palette::NAMES)
+                .default_value("classic")
+                .help("A named colour palette"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .takes_value(true)
+                .help("A JSON file with defaults for the colour options, instead of ~/.config/chip8-rs/config.json"),
+        )
+        .arg(
+            Arg::with_name("colors")
+                .long("colors")
+                .takes_value(true)
+                .use_delimiter(true)
+                .max_values(4)
+                .help("Up to four comma separated colours, e.g. for XO-CHIP (#rrggbb)"),
+        )
+        .arg(
+            Arg::with_name("foreground")
+                .long("foreground")
+                .takes_value(true)
+                .help("Foreground colour (#rrggbb)"),
+        )
+        .arg(
+            Arg::with_name("background")
+                .long("background")
+                .takes_value(true)
+                .help("Background colour (#rrggbb)"),
+        )
+        .subcommand(
+            SubCommand::with_name("bench")
+                .about("Measure how fast the engines run built-in workloads")
+                .arg(
+                    Arg::with_name("instructions")
+                        .short("n")
+                        .long("instructions")
+                        .takes_value(true)
+                        .default_value("10")
+                        .help("Millions of instructions to run per workload and engine"),
+                )
+                .arg(
+                    Arg::with_name("engine")
+                        .long("engine")
+                        .takes_value(true)
+                        .multiple(true)
+                        .possible_values(&engine::NAMES)
+                        .help("Only measure these engines"),
+                )
+                .arg(
+                    Arg::with_name("workload")
+                        .long("workload")
+                        .takes_value(true)
+                        .multiple(true)
+                        .possible_values(&WORKLOADS.map(|workload| workload.name))
+                        .help("Only run these workloads"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("analyze")
+                .about("List the code and data of a rom, found by following its control flow")
+                .arg(
+                    Arg::with_name("cartridge")
+                        .short("c")
+                        .takes_value(true)
+                        .required(true)
+                        .help("A cartridge binary file, an Octo cartridge gif or a zip archive"),
+                )
+                .arg(
+                    Arg::with_name("entry")
+                        .short("e")
+                        .long("entry")
+                        .takes_value(true)
+                        .help("The rom to pick out of a zip archive"),
+                )
+                .arg(
+                    Arg::with_name("platform")
+                        .long("platform")
+                        .takes_value(true)
+                        .possible_values(&platform::NAMES)
+                        .help("Platform the rom runs on, instead of looking the rom up"),
+                )
+                .arg(
+                    Arg::with_name("database")
+                        .long("database")
+                        .takes_value(true)
+                        .help("A programs.json of the chip-8-database, instead of the bundled one"),
+                )
+                .arg(
+                    Arg::with_name("load-address")
+                        .long("load-address")
+                        .takes_value(true)
+                        .help("Address the rom gets loaded at and starts [default: 0x200, depends on the platform]"),
+                )
+                .arg(
+                    Arg::with_name("dot")
+                        .long("dot")
+                        .takes_value(true)
+                        .help("Write the control flow graph for Graphviz to a file, - for stdout"),
+                ),
         )
         .get_matches();
 
+    if let Some(app) = app.subcommand_matches("bench") {
+        return run_bench(app);
+    }
+    if let Some(app) = app.subcommand_matches("analyze") {
+        return run_analyze(app);
+    }
+
     let game_file = app
         .value_of("cartridge")
         .ok_or(ChipError::CartridgeNotFound)?;
 
+    let mut cartridge = match Cartridge::new(game_file, app.value_of("entry")) {
+        // let the user pick a rom out of the archive, unless stdin is already taken
+        Err(CartridgeError::MultipleEntries(names)) if game_file != "-" => {
+            let entry = choose_entry(&names).ok_or(ChipError::NoEntryChosen)?;
+            Cartridge::new(game_file, Some(&entry))
+        }
+        cartridge => cartridge,
+    }
+    .map_err(ChipError::Cartridge)?;
+
+    // settings coming with the cartridge win, otherwise look the rom up
+    let info = match cartridge.info.take() {
+        Some(info) => Some(info),
+        None => {
+            let database = match app.value_of("database") {
+                Some(filename) => Database::from_file(filename).map_err(ChipError::Database)?,
+                None => Database::bundled(),
+            };
+            database.lookup(&cartridge.sha1())
+        }
+    };
+
+    // the config fills in colour options missing on the command line
+    let config = match app.value_of("config") {
+        Some(filename) => Config::from_file(filename),
+        None => Config::load_default(),
+    }
+    .map_err(ChipError::Config)?;
+
+    // start with a named palette or the one of the rom, then override single colours
+    let palette_name = match (app.occurrences_of("palette"), &config.palette) {
+        (0, Some(name)) => Some(name.as_str()),
+        (0, None) => None,
+        _ => app.value_of("palette"),
+    };
+    let mut palette = match palette_name {
+        Some(name) => {
+            Palette::from_name(name).ok_or_else(|| ChipError::InvalidPalette(name.to_string()))?
+        }
+        None => Palette::default(),
+    };
+    if let (None, Some(info)) = (palette_name, &info) {
+        for (pos, &color) in info.colors.iter().take(4).enumerate() {
+            palette.colors[pos] = color;
+        }
+    }
+    let colors: Option<Vec<&str>> = match app.values_of("colors") {
+        Some(colors) => Some(colors.collect()),
+        None => config
+            .colors
+            .as_ref()
+            .map(|colors| colors.iter().map(String::as_str).collect()),
+    };
+    for (pos, color) in colors.unwrap_or_default().into_iter().take(4).enumerate() {
+        palette.colors[pos] = parse_color(color)?;
+    }
+    if let Some(color) = app.value_of("background").or(config.background.as_deref()) {
+        palette.colors[0] = parse_color(color)?;
+    }
+    if let Some(color) = app.value_of("foreground").or(config.foreground.as_deref()) {
+        palette.colors[1] = parse_color(color)?;
+    }
+
+    let scale = match app.value_of("scale") {
+        Some(scale) => match scale.parse() {
+            Ok(scale) if scale > 0 => scale,
+            _ => return Err(ChipError::InvalidScale(scale.to_string())),
+        },
+        None => SCREEN_SCALE,
+    };
+
+    let filter = app
+        .value_of("filter")
+        .and_then(Filter::from_name)
+        .unwrap_or(Filter::None);
+
+    // a given platform wins over the database
+    let platform = app.value_of("platform").and_then(Platform::from_name);
+    let quirks = match platform {
+        Some(platform) => platform.quirks(),
+        None => info
+            .as_ref()
+            .and_then(|info| info.quirks)
+            .unwrap_or_default(),
+    };
+    let platform = platform.or_else(|| info.as_ref().and_then(|info| info.platform));
+    let memory_size = platform
+        .map(|platform| platform.memory_size())
+        .unwrap_or(MEMORY_SIZE);
+
+    let load_address = load_address(&app, platform, memory_size, cartridge.data().len())?;
+
+    // the big font follows right after the small one
+    let (font, big_font) = match app.value_of("font-file") {
+        Some(filename) => {
+            let data = fs::read(filename).map_err(ChipError::Font)?;
+            match data.len() {
+                FONT_SIZE => (data, BIG_FONT.to_vec()),
+                size if size == FONT_SIZE + BIG_FONT_SIZE => {
+                    let big_font = data[FONT_SIZE..].to_vec();
+                    (data[..FONT_SIZE].to_vec(), big_font)
+                }
+                size => return Err(ChipError::InvalidFontSize(size)),
+            }
+        }
+        None => {
+            let font = app
+                .value_of("font")
+                .and_then(Font::from_name)
+                .or_else(|| platform.map(|platform| platform.font()))
+                .unwrap_or(Font::Classic);
+            (font.data().to_vec(), BIG_FONT.to_vec())
+        }
+    };
+
+    let font_size = font.len() + big_font.len();
+    let font_address = match app.value_of("font-address") {
+        Some(address) => match parse_address(address) {
+            Some(address)
+                if address
+                    .checked_add(font_size)
+                    .is_some_and(|end| end <= memory_size) =>
+            {
+                address
+            }
+            _ => return Err(ChipError::InvalidFontAddress(address.to_string())),
+        },
+        None => 0,
+    };
+    // the font has to stay out of the way of the rom, it can go before or behind it
+    if font_address < load_address && font_address + font_size > load_address {
+        return Err(ChipError::FontOverlap(font_address, load_address));
+    }
+
+    let cycles_per_frame = match app.value_of("tickrate") {
+        Some(tickrate) => match tickrate.parse() {
+            Ok(tickrate) if tickrate > 0 => tickrate,
+            _ => return Err(ChipError::InvalidTickrate(tickrate.to_string())),
+        },
+        None => info
+            .as_ref()
+            .and_then(|info| info.tickrate)
+            .unwrap_or(CYCLES_PER_FRAME),
+    };
+
+    let title = match &info {
+        Some(info) => format!("chip8-rs - {}", info.title),
+        None => "chip8-rs".to_string(),
+    };
+    let controls = info.map(|info| info.controls).unwrap_or_default();
+
     let mut processor = Processor::new();
+    processor.set_quirks(quirks);
+    processor.set_engine(
+        app.value_of("engine")
+            .and_then(Engine::from_name)
+            .unwrap_or(Engine::Interpreter),
+    );
+    processor.set_memory_size(memory_size);
+    processor.set_load_address(load_address);
+    processor.set_font(&font, &big_font, font_address);
+
+    // octo cartridges get assembled for where they are loaded
+    cartridge
+        .relocate(load_address)
+        .map_err(ChipError::Cartridge)?;
 
-    let cartridge = Cartridge::new(&game_file);
+    // the rom has to fit behind the entry point, in front of a font behind it
+    let max_size = processor.max_rom_size();
+    cartridge.validate(max_size).map_err(ChipError::Cartridge)?;
 
-    processor.start(&cartridge.rom);
+    let cheat_directory = app.value_of("cheats");
+    let cheats = match cheat_directory {
+        Some(directory) => {
+            CheatList::load(directory, &cartridge.sha1()).map_err(ChipError::Cheats)?
+        }
+        None => CheatList::new(),
+    };
+
+    #[cfg(feature = "scripting")]
+    let script = match app.value_of("script") {
+        Some(filename) => {
+            Some(Script::from_file(filename, &mut processor).map_err(ChipError::Script)?)
+        }
+        None => None,
+    };
+    #[cfg(not(feature = "scripting"))]
+    if app.is_present("script") {
+        return Err(ChipError::ScriptingDisabled);
+    }
+
+    let remote = match app.value_of("remote") {
+        Some("-") if game_file == "-" || app.is_present("terminal") => {
+            return Err(ChipError::StdioTaken)
+        }
+        Some("-") => Some(Server::stdio()),
+        #[cfg(unix)]
+        Some(path) => Some(Server::unix(path).map_err(ChipError::Remote)?),
+        #[cfg(not(unix))]
+        Some(_) => return Err(ChipError::SocketsUnsupported),
+        None => None,
+    };
+
+    let mut frontend: Box<dyn Frontend> = if app.is_present("terminal") {
+        let terminal = Terminal::new(palette, filter, controls, &title);
+        Box::new(terminal.map_err(ChipError::Terminal)?)
+    } else {
+        Box::new(SdlFrontend::new(
+            DisplayOptions {
+                title,
+                palette,
+                scale,
+                integer_scale: app.is_present("integer-scale"),
+                fullscreen: app.is_present("fullscreen"),
+                filter,
+            },
+            controls,
+        ))
+    };
+
+    // stdin can't be read twice
+    let watcher = if app.is_present("watch") && game_file != "-" {
+        Some(Watcher::new(
+            game_file,
+            app.value_of("entry"),
+            load_address,
+            max_size,
+        ))
+    } else {
+        None
+    };
+
+    processor.start(
+        cartridge.data(),
+        frontend.as_mut(),
+        cycles_per_frame,
+        Hooks {
+            watcher,
+            #[cfg(feature = "scripting")]
+            script,
+            cheats,
+            cheat_directory: cheat_directory.map(String::from),
+            remote,
+        },
+    );
+    Ok(())
+}
+
+// print the MIPS of every workload and engine
+fn run_bench(app: &ArgMatches) -> Result<(), ChipError> {
+    let millions = app.value_of("instructions").unwrap_or("10");
+    let instructions = match millions.parse::<usize>() {
+        Ok(millions) if millions > 0 => millions * 1_000_000,
+        _ => return Err(ChipError::InvalidInstructions(millions.to_string())),
+    };
+    let engines: Vec<&str> = match app.values_of("engine") {
+        Some(engines) => engines.collect(),
+        None => engine::NAMES.to_vec(),
+    };
+    let workloads: Vec<&str> = match app.values_of("workload") {
+        Some(workloads) => workloads.collect(),
+        None => WORKLOADS.iter().map(|workload| workload.name).collect(),
+    };
+
+    println!("{:<10} {:<12} {:>10}", "workload", "engine", "MIPS");
+    for workload in workloads.iter().filter_map(|name| bench::workload(name)) {
+        for (name, engine) in engines
+            .iter()
+            .filter_map(|name| Some((name, Engine::from_name(name)?)))
+        {
+            let mips = bench::mips(workload.rom, engine, instructions);
+            println!("{:<10} {:<12} {:>10.1}", workload.name, name, mips);
+        }
+    }
     Ok(())
 }
+
+// print a listing of the rom and its findings, or the graph only
+fn run_analyze(app: &ArgMatches) -> Result<(), ChipError> {
+    let game_file = app
+        .value_of("cartridge")
+        .ok_or(ChipError::CartridgeNotFound)?;
+    let mut cartridge =
+        Cartridge::new(game_file, app.value_of("entry")).map_err(ChipError::Cartridge)?;
+
+    // the platform decides where the rom lives and what bnnn adds, like when running it
+    let info = match cartridge.info.take() {
+        Some(info) => Some(info),
+        None => {
+            let database = match app.value_of("database") {
+                Some(filename) => Database::from_file(filename).map_err(ChipError::Database)?,
+                None => Database::bundled(),
+            };
+            database.lookup(&cartridge.sha1())
+        }
+    };
+    let platform = app.value_of("platform").and_then(Platform::from_name);
+    let quirks = match platform {
+        Some(platform) => platform.quirks(),
+        None => info
+            .as_ref()
+            .and_then(|info| info.quirks)
+            .unwrap_or_default(),
+    };
+    let platform = platform.or_else(|| info.and_then(|info| info.platform));
+    let memory_size = platform
+        .map(|platform| platform.memory_size())
+        .unwrap_or(MEMORY_SIZE);
+    let load_address = load_address(app, platform, memory_size, cartridge.data().len())?;
+    cartridge
+        .relocate(load_address)
+        .map_err(ChipError::Cartridge)?;
+    let analysis = Analysis::new(cartridge.data(), load_address, quirks);
+
+    match app.value_of("dot") {
+        Some("-") => print!("{}", analysis.to_dot()),
+        Some(filename) => {
+            fs::write(filename, analysis.to_dot()).map_err(ChipError::Output)?;
+            print!("{}", analysis.listing());
+        }
+        None => print!("{}", analysis.listing()),
+    }
+
+    // findings go to stderr, so the graph on stdout stays usable
+    let addresses = |set: &BTreeSet<usize>| {
+        let list: Vec<String> = set
+            .iter()
+            .map(|address| format!("{:#05x}", address))
+            .collect();
+        list.join(" ")
+    };
+    let findings = [
+        ("subroutines", analysis.subroutines()),
+        ("computed jumps (bnnn)", analysis.computed_jumps()),
+        ("unknown opcodes", analysis.unknown()),
+        ("jumps out of the rom", analysis.outside()),
+    ];
+    eprintln!();
+    for (name, set) in findings.iter().filter(|(_, set)| !set.is_empty()) {
+        eprintln!("{}: {}", name, addresses(set));
+    }
+    for (range, _) in analysis
+        .regions()
+        .into_iter()
+        .filter(|(_, region)| *region == Region::Unreachable)
+    {
+        eprintln!(
+            "unreachable code: {:#05x}-{:#05x}",
+            range.start,
+            range.end - 1
+        );
+    }
+    Ok(())
+}
+
+// ask which rom of an archive to play
+fn choose_entry(names: &[String]) -> Option<String> {
+    eprintln!("The archive contains several roms:");
+    for (pos, name) in names.iter().enumerate() {
+        eprintln!("{:>3}: {}", pos + 1, name);
+    }
+    eprint!("Pick one [1-{}]: ", names.len());
+
+    let mut line = String::new();
+    io::stdin().read_line(&mut line).ok()?;
+    let choice: usize = line.trim().parse().ok()?;
+    names.get(choice.checked_sub(1)?).cloned()
+}
+
+// hex with a leading 0x, otherwise decimal
+// --load-address or the one of the platform, with room for the rom behind it
+fn load_address(
+    app: &ArgMatches,
+    platform: Option<Platform>,
+    memory_size: usize,
+    rom_size: usize,
+) -> Result<usize, ChipError> {
+    match app.value_of("load-address") {
+        Some(address) => match parse_address(address) {
+            Some(address)
+                if address
+                    .checked_add(rom_size)
+                    .is_some_and(|end| end <= memory_size) =>
+            {
+                Ok(address)
+            }
+            _ => Err(ChipError::InvalidLoadAddress(address.to_string())),
+        },
+        None => Ok(platform
+            .map(|platform| platform.load_address())
+            .unwrap_or(GAME_ENTRY)),
+    }
+}
+
+fn parse_address(address: &str) -> Option<usize> {
+    match address.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => address.parse().ok(),
+    }
+}
+
+fn parse_color(color: &str) -> Result<u32, ChipError> {
+    palette::parse_color(color).ok_or_else(|| ChipError::InvalidColor(color.to_string()))
+}
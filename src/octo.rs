@@ -0,0 +1,279 @@
+// Octo shares programs as "cartridges", GIF images of a cartridge label.
+// The low nibble of every pixel's colour index carries data, two pixels per
+// byte with the high nibble first. The data is a 32bit big endian length,
+// followed by JSON holding the program's source and its options.
+
+use std::io::Cursor;
+
+use serde::Deserialize;
+
+use crate::assembler::{self, AssemblerError};
+use crate::database::RomInfo;
+use crate::input::Controls;
+use crate::palette;
+use crate::platform::{Platform, Quirks};
+
+pub const GIF_MAGIC: &[&[u8]] = &[b"GIF87a", b"GIF89a"];
+
+// fields are only read by the Debug output of main
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum OctoError {
+    Gif(gif::DecodingError),
+    Truncated,
+    Json(serde_json::Error),
+    Source(AssemblerError),
+}
+
+#[derive(Deserialize)]
+struct Payload {
+    program: String,
+    #[serde(default)]
+    options: Options,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct Options {
+    tickrate: Option<usize>,
+    background_color: Option<String>,
+    fill_color: Option<String>,
+    fill_color2: Option<String>,
+    blend_color: Option<String>,
+    shift_quirks: Option<bool>,
+    load_store_quirks: Option<bool>,
+    clip_quirks: Option<bool>,
+    v_blank_quirks: Option<bool>,
+    jump_quirks: Option<bool>,
+    logic_quirks: Option<bool>,
+    max_size: Option<usize>,
+}
+
+pub struct OctoCartridge {
+    pub rom: Vec<u8>,   // assembled for origin
+    pub source: String, // to assemble it again for another origin
+    pub info: RomInfo,
+}
+
+// origin is the address the rom gets loaded to
+pub fn decode(gif: &[u8], title: &str, origin: usize) -> Result<OctoCartridge, OctoError> {
+    let data = payload(gif)?;
+    let payload: Payload = serde_json::from_slice(&data).map_err(OctoError::Json)?;
+    let rom = assembler::assemble(&payload.program, origin).map_err(OctoError::Source)?;
+    let options = payload.options;
+
+    // octo sizes memory by the biggest program it allows
+    let platform = match options.max_size {
+        Some(max_size) if max_size > 4096 => Some(Platform::XoChip),
+        _ => None,
+    };
+
+    let defaults = Quirks::default();
+    let quirks = Quirks {
+        vf_reset: options.logic_quirks.unwrap_or(defaults.vf_reset),
+        shift_in_place: options.shift_quirks.unwrap_or(defaults.shift_in_place),
+        increment_index: options
+            .load_store_quirks
+            .map(|quirk| !quirk)
+            .unwrap_or(defaults.increment_index),
+        jump_vx: options.jump_quirks.unwrap_or(defaults.jump_vx),
+        clip_sprites: options.clip_quirks.unwrap_or(defaults.clip_sprites),
+        display_wait: options.v_blank_quirks.unwrap_or(defaults.display_wait),
+    };
+
+    let colors = [
+        &options.background_color,
+        &options.fill_color,
+        &options.fill_color2,
+        &options.blend_color,
+    ];
+
+    Ok(OctoCartridge {
+        rom,
+        source: payload.program,
+        info: RomInfo {
+            title: title.to_string(),
+            platform,
+            quirks: Some(quirks),
+            tickrate: options.tickrate,
+            controls: Controls::default(),
+            colors: colors
+                .iter()
+                .map_while(|color| color.as_deref().and_then(palette::parse_color))
+                .collect(),
+        },
+    })
+}
+
+// the bytes hidden in the pixels of all frames
+fn payload(gif: &[u8]) -> Result<Vec<u8>, OctoError> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options
+        .read_info(Cursor::new(gif))
+        .map_err(OctoError::Gif)?;
+
+    let mut nibbles = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().map_err(OctoError::Gif)? {
+        nibbles.extend(frame.buffer.iter().map(|index| index & 0x0f));
+    }
+
+    let bytes: Vec<u8> = nibbles
+        .chunks_exact(2)
+        .map(|pair| pair[0] << 4 | pair[1])
+        .collect();
+    if bytes.len() < 4 {
+        return Err(OctoError::Truncated);
+    }
+    let size = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+    bytes
+        .get(4..4 + size)
+        .map(|data| data.to_vec())
+        .ok_or(OctoError::Truncated)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    // hide data in a gif the way octo does
+    pub(crate) fn encode(json: &str) -> Vec<u8> {
+        let mut data = (json.len() as u32).to_be_bytes().to_vec();
+        data.extend(json.as_bytes());
+
+        let mut pixels: Vec<u8> = data
+            .iter()
+            .flat_map(|byte| vec![0x10 | byte >> 4, 0x20 | byte & 0x0f])
+            .collect();
+        let width = 64;
+        pixels.resize((pixels.len() / width + 1) * width, 0);
+        let height = (pixels.len() / width) as u16;
+
+        let palette = [0; 256 * 3];
+        let mut gif = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut gif, width as u16, height, &palette).unwrap();
+            let frame = gif::Frame {
+                width: width as u16,
+                height,
+                buffer: Cow::Borrowed(&pixels),
+                ..gif::Frame::default()
+            };
+            encoder.write_frame(&frame).unwrap();
+        }
+        gif
+    }
+
+    // a program like the ones shared on the octo archive
+    const BOUNCE: &str = "# a ball bouncing off the walls
+:calc WIDTH { 64 - 8 }
+:const BOTTOM 28
+:alias ball-x v1
+:alias ball-y v2
+:alias speed v3
+:alias score v4
+
+:macro pause frames {
+  vf := frames
+  delay := vf
+  loop
+    vf := delay
+    while vf != 0
+  again
+}
+
+: ball
+  0b00111100 0b01111110 0b01111110 0b00111100
+
+: draw-ball
+  i := ball
+  sprite ball-x ball-y 4
+;
+
+: main
+  clear
+  ball-x := 0
+  ball-y := 12
+  speed := 1
+  loop
+    draw-ball
+    ball-x += speed
+    if ball-x == WIDTH then speed := -1
+    if ball-x == 0 begin
+      speed := 1
+      ball-y += 2
+    else
+      score += 1
+    end
+    draw-ball
+    pause 2
+    if ball-y >= BOTTOM then jump game-over
+  again
+
+: game-over
+  i := hex score
+  sprite ball-x ball-y 5
+  loop again
+";
+
+    #[test]
+    fn test_decode() {
+        let gif = encode(
+            &serde_json::json!({
+                "program": BOUNCE,
+                "options": {
+                    "tickrate": 200,
+                    "backgroundColor": "#996600",
+                    "fillColor": "#FFCC00",
+                    "shiftQuirks": false,
+                    "loadStoreQuirks": true,
+                    "vBlankQuirks": true,
+                    "maxSize": 65024
+                }
+            })
+            .to_string(),
+        );
+        let cartridge = decode(&gif, "bounce", crate::GAME_ENTRY).unwrap();
+        #[rustfmt::skip]
+        assert_eq!(
+            cartridge.rom,
+            vec![
+                0x12, 0x0c, // jump main
+                0x3c, 0x7e, 0x7e, 0x3c, // ball
+                0xa2, 0x02, 0xd1, 0x24, 0x00, 0xee, // draw-ball
+                0x00, 0xe0, 0x61, 0x00, 0x62, 0x0c, 0x63, 0x01, // main
+                0x22, 0x06, 0x81, 0x34, 0x41, 0x38, 0x63, 0xff,
+                0x31, 0x00, 0x12, 0x26, 0x63, 0x01, 0x72, 0x02, 0x12, 0x28, 0x74, 0x01,
+                0x22, 0x06, 0x6f, 0x02, 0xff, 0x15, 0xff, 0x07, 0x4f, 0x00, 0x12, 0x36, 0x12, 0x2e,
+                0x6f, 0x1c, 0x8f, 0x27, 0x4f, 0x01, 0x12, 0x40, 0x12, 0x14,
+                0xf4, 0x29, 0xd1, 0x25, 0x12, 0x44, // game-over
+            ]
+        );
+        assert_eq!(cartridge.info.title, "bounce");
+        assert_eq!(cartridge.info.tickrate, Some(200));
+        assert_eq!(cartridge.info.colors, vec![0x996600, 0xffcc00]);
+        assert_eq!(cartridge.info.platform, Some(Platform::XoChip));
+
+        let quirks = cartridge.info.quirks.unwrap();
+        assert!(!quirks.shift_in_place);
+        assert!(!quirks.increment_index);
+        assert!(quirks.display_wait);
+    }
+
+    #[test]
+    fn test_source_error() {
+        let gif = encode(r#"{ "program": ": main\n  v0 := 1\n  jump nowhere" }"#);
+        match decode(&gif, "broken", crate::GAME_ENTRY) {
+            Err(OctoError::Source(error)) => assert_eq!(error.line, 3),
+            _ => panic!("the source should be broken"),
+        }
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut gif = encode(r#"{ "program": "" }"#);
+        gif.truncate(20);
+        assert!(decode(&gif, "broken", crate::GAME_ENTRY).is_err());
+    }
+}
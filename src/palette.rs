@@ -0,0 +1,70 @@
+// colours are stored as 0xRRGGBB
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    // background, foreground and the two extra XO-CHIP plane colours
+    pub colors: [u32; 4],
+}
+
+pub const NAMES: [&str; 6] = ["classic", "phosphor", "amber", "lcd", "octo", "contrast"];
+
+impl Palette {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let colors = match name {
+            // white on black
+            "classic" => [0x000000, 0xffffff, 0xaaaaaa, 0x555555],
+            // green phosphor tube
+            "phosphor" => [0x0a140a, 0x33ff66, 0x1a8033, 0x99ffb3],
+            // amber monochrome monitor
+            "amber" => [0x140a00, 0xffb000, 0x805800, 0xffd780],
+            // greenish handheld lcd, as in octo
+            "lcd" => [0xf9ffb3, 0x3d8026, 0xabcc47, 0x00131a],
+            // octo defaults
+            "octo" => [0x996600, 0xffcc00, 0xff6600, 0x662200],
+            // high contrast yellow on black
+            "contrast" => [0x000000, 0xffff00, 0x00ffff, 0xffffff],
+            _ => return None,
+        };
+        Some(Palette { colors })
+    }
+
+    // colour for a pixel value of the screen, which is a plane bitmask
+    pub fn color(&self, pixel: u8) -> u32 {
+        self.colors[(pixel & 0b11) as usize]
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::from_name("classic").unwrap()
+    }
+}
+
+// parse colours like "#ff8800" or "ff8800"
+pub fn parse_color(hex: &str) -> Option<u32> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_named_palettes() {
+        for name in NAMES.iter() {
+            assert!(Palette::from_name(name).is_some());
+        }
+        assert_eq!(Palette::from_name("rainbow"), None);
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#ff8800"), Some(0xff8800));
+        assert_eq!(parse_color("00ff00"), Some(0x00ff00));
+        assert_eq!(parse_color("#fff"), None);
+        assert_eq!(parse_color("zzzzzz"), None);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::fontset::Font;
+
+// Behaviour, which differs between chip8 interpreters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    pub vf_reset: bool,        // 8xy1, 8xy2 and 8xy3 reset VF
+    pub shift_in_place: bool,  // 8xy6 and 8xye shift Vx instead of Vy
+    pub increment_index: bool, // fx55 and fx65 leave I behind the last register
+    pub jump_vx: bool,         // bnnn jumps to xnn + Vx instead of nnn + V0
+    pub clip_sprites: bool,    // sprites get cut off at the edges instead of wrapping
+    pub display_wait: bool,    // dxyn waits for the next frame
+}
+
+impl Default for Quirks {
+    // lenient mix, which most games work with
+    fn default() -> Self {
+        Quirks {
+            vf_reset: false,
+            shift_in_place: true,
+            increment_index: false,
+            jump_vx: false,
+            clip_sprites: false,
+            display_wait: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Chip8,  // original COSMAC VIP interpreter
+    Schip,  // SUPER-CHIP on HP48 calculators
+    XoChip, // Octo's extension
+    Eti660, // ETI 660 learning computer
+}
+
+pub const NAMES: [&str; 4] = ["chip8", "schip", "xochip", "eti660"];
+
+impl Platform {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "chip8" => Some(Platform::Chip8),
+            "schip" => Some(Platform::Schip),
+            "xochip" => Some(Platform::XoChip),
+            "eti660" => Some(Platform::Eti660),
+            _ => None,
+        }
+    }
+
+    pub fn memory_size(&self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::Schip | Platform::Eti660 => 4096,
+            Platform::XoChip => 65536,
+        }
+    }
+
+    // where games get loaded and start
+    pub fn load_address(&self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::Schip | Platform::XoChip => crate::GAME_ENTRY,
+            Platform::Eti660 => 0x600,
+        }
+    }
+
+    pub fn font(&self) -> Font {
+        match self {
+            Platform::Chip8 | Platform::Schip | Platform::XoChip => Font::Classic,
+            Platform::Eti660 => Font::Eti660,
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Platform::Chip8 | Platform::Eti660 => Quirks {
+                vf_reset: true,
+                shift_in_place: false,
+                increment_index: true,
+                jump_vx: false,
+                clip_sprites: true,
+                display_wait: true,
+            },
+            Platform::Schip => Quirks {
+                vf_reset: false,
+                shift_in_place: true,
+                increment_index: false,
+                jump_vx: true,
+                clip_sprites: true,
+                display_wait: false,
+            },
+            Platform::XoChip => Quirks {
+                vf_reset: false,
+                shift_in_place: false,
+                increment_index: true,
+                jump_vx: false,
+                clip_sprites: false,
+                display_wait: false,
+            },
+        }
+    }
+}
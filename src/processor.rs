@@ -1,17 +1,93 @@
-use sdl2;
+// the opcodes and their tests keep the style they were written in
+#![allow(
+    clippy::assign_op_pattern,
+    clippy::unnecessary_cast,
+    clippy::bool_assert_comparison
+)]
 
 use rand::Rng;
 use std::thread;
-use std::time::Duration;
-
-use crate::display::Display;
-use crate::fontset::FONT;
-use crate::input::Input;
+use std::time::{Duration, Instant};
+
+use crate::cheat::CheatList;
+use crate::engine::Engine;
+use crate::fontset::{BIG_FONT, BIG_FONT_SIZE, FONT};
+use crate::frontend::Frontend;
+use crate::input::{InputEvent, KeyEvent};
+use crate::instruction::Instruction;
+use crate::jit::Jit;
+use crate::platform::{Platform, Quirks};
+use crate::remote::{Control, Server};
+#[cfg(feature = "scripting")]
+use crate::script::Script;
+use crate::speed::Speed;
+use crate::state::{Reader, StateError, Writer};
+use crate::watch::Watcher;
 
 const OPCODE_SIZE: usize = 2;
 
+const STATE_MAGIC: &[u8] = b"C8ST";
+const STATE_VERSION: u8 = 1;
+
+// What runs next to the game in the main loop, all of it optional
+#[derive(Default)]
+pub struct Hooks {
+    pub watcher: Option<Watcher>, // swaps in a new version of the game, whenever its file changes
+    #[cfg(feature = "scripting")]
+    pub script: Option<Script>,
+    pub cheats: CheatList,               // written again after every frame
+    pub cheat_directory: Option<String>, // of the cheats for roms loaded later on
+    pub remote: Option<Server>,
+}
+
+impl Hooks {
+    #[cfg(feature = "scripting")]
+    fn key_event(&mut self, processor: &mut Processor, key_event: KeyEvent) {
+        if let Some(script) = &mut self.script {
+            script.key_event(processor, key_event);
+        }
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    fn key_event(&mut self, _processor: &mut Processor, _key_event: KeyEvent) {}
+
+    // a frame of the main loop, with the hooks of the script and the cheats
+    pub fn run_frame(&mut self, processor: &mut Processor, cycles_per_frame: usize) -> bool {
+        #[cfg(feature = "scripting")]
+        let redraw = match &mut self.script {
+            Some(script) => script.run_frame(processor, cycles_per_frame),
+            None => processor.run_frame(cycles_per_frame),
+        };
+        #[cfg(not(feature = "scripting"))]
+        let redraw = processor.run_frame(cycles_per_frame);
+        self.cheats.apply(processor);
+        redraw
+    }
+
+    // after a rom got loaded, or loaded again
+    #[cfg(feature = "scripting")]
+    pub fn game_loaded(&mut self, processor: &mut Processor) {
+        if let Some(script) = &mut self.script {
+            script.load(processor);
+        }
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    pub fn game_loaded(&mut self, _processor: &mut Processor) {}
+
+    #[cfg(feature = "scripting")]
+    fn overlay(&self) -> Option<String> {
+        self.script.as_ref().and_then(|script| script.overlay())
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    fn overlay(&self) -> Option<String> {
+        None
+    }
+}
+
 pub struct Processor {
-    memory: [u8; crate::MEMORY_SIZE],
+    memory: Vec<u8>,
     register: [u8; 16], // general purpose registers
     index: usize,       // used to store memory addresses
     pc: usize,          // programm counter
@@ -24,18 +100,27 @@ pub struct Processor {
     key: [bool; 16], // bool table for keyinputs
     waiting_for_key: bool,
     waiting_key_location: usize,
+    waiting_key_pressed: Option<usize>, // key pressed while waiting
+    waiting_for_frame: bool,            // dxyn waits for the next frame
+    quirks: Quirks,
+    load_address: usize,     // games get loaded and start here
+    font_address: usize,     // small font sprites used by fx29
+    big_font_address: usize, // big font sprites used by fx30
+    engine: Engine,
+    decoded: Vec<Option<Instruction>>, // instruction starting at each address, cached engine only
+    jit: Option<Jit>,
+}
+
+impl Default for Processor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Processor {
     pub fn new() -> Self {
-        let mut mem = [0; crate::MEMORY_SIZE];
-        // load font
-        for (pos, &val) in FONT.iter().enumerate() {
-            mem[pos] = val;
-        }
-
-        Processor {
-            memory: mem,
+        let mut processor = Processor {
+            memory: vec![0; crate::MEMORY_SIZE],
             register: [0; 16],
             index: 0,
             pc: crate::GAME_ENTRY,
@@ -48,75 +133,493 @@ impl Processor {
             key: [false; 16],
             waiting_for_key: false,
             waiting_key_location: 0,
+            waiting_key_pressed: None,
+            waiting_for_frame: false,
+            quirks: Quirks::default(),
+            load_address: crate::GAME_ENTRY,
+            font_address: 0,
+            big_font_address: 0,
+            engine: Engine::Interpreter,
+            decoded: Vec::new(),
+            jit: None,
+        };
+        processor.set_font(&FONT, &BIG_FONT, 0);
+        processor
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+        // compiled code depends on the quirks
+        if let Some(jit) = &mut self.jit {
+            jit.set_quirks(quirks);
         }
     }
 
-    pub fn start(&mut self, game: &[u8]) {
-        let sdl_ctx = sdl2::init().unwrap();
-        let mut display = Display::new(&sdl_ctx);
-        let mut input = Input::new(&sdl_ctx);
+    // without a jit for this platform, the cached engine runs instead
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+        self.jit = match engine {
+            Engine::Jit => Jit::new(self.quirks),
+            _ => None,
+        };
+        self.forget_code();
+    }
+
+    // some platforms have more than 4KB of memory
+    pub fn set_memory_size(&mut self, size: usize) {
+        self.memory.resize(size, 0);
+        self.forget_code();
+    }
 
+    // some platforms load games somewhere else than 0x200
+    pub fn set_load_address(&mut self, address: usize) {
+        self.load_address = address;
+        self.pc = address;
+    }
+
+    // bytes a rom can take up, up to the end of memory or a font behind the load address
+    pub fn max_rom_size(&self) -> usize {
+        if self.font_address >= self.load_address {
+            self.font_address - self.load_address
+        } else {
+            self.memory.len() - self.load_address
+        }
+    }
+
+    // store the font at address, followed by the big font
+    pub fn set_font(&mut self, font: &[u8], big_font: &[u8], address: usize) {
+        let big_font_address = address + font.len();
+        self.memory[address..big_font_address].copy_from_slice(font);
+        self.memory[big_font_address..big_font_address + big_font.len()].copy_from_slice(big_font);
+        self.font_address = address;
+        self.big_font_address = big_font_address;
+        self.forget_code();
+    }
+
+    // everything a platform differs in, for embedders without a database or command line
+    pub fn set_platform(&mut self, platform: Platform) {
+        self.set_quirks(platform.quirks());
+        self.set_memory_size(platform.memory_size());
+        self.set_load_address(platform.load_address());
+        self.set_font(platform.font().data(), &BIG_FONT, 0);
+    }
+
+    pub fn start(
+        &mut self,
+        game: &[u8],
+        frontend: &mut dyn Frontend,
+        cycles_per_frame: usize,
+        mut hooks: Hooks,
+    ) {
         // load binary file
+        let mut game = game.to_vec();
         self.load_game(&game);
+        hooks.game_loaded(self);
+
+        let frame_time = Duration::from_secs(1) / crate::FRAME_RATE;
+        let mut speed = Speed::new();
+        let mut search = None; // of remote calls
+        let mut status = None;
+
+        while let Ok(input_events) = frontend.fetch() {
+            let frame_start = Instant::now();
+
+            // apply key presses and releases from loop
+            let mut redraw = false;
+            for input_event in input_events {
+                match input_event {
+                    InputEvent::Key(key_event) => {
+                        self.key_event(key_event);
+                        hooks.key_event(self, key_event);
+                    }
+                    InputEvent::Redraw => redraw = true,
+                    InputEvent::Reset => {
+                        self.reset();
+                        self.load_game(&game);
+                        hooks.game_loaded(self);
+                        redraw = true;
+                    }
+                    input_event => {
+                        if speed.handle(input_event) {
+                            redraw = true;
+                        }
+                    }
+                }
+            }
+
+            if let Some(new_game) = hooks.watcher.as_mut().and_then(|watcher| watcher.poll()) {
+                game = new_game;
+                self.reset();
+                self.load_game(&game);
+                hooks.game_loaded(self);
+                redraw = true;
+            }
 
-        while let Ok(key) = input.fetch() {
-            // get keypress from loop
-            self.key = key;
+            // remote calls come in between two frames
+            if let Some(remote) = hooks.remote.take() {
+                let max_size = self.max_rom_size();
+                for call in remote.poll() {
+                    call.answer(&mut Control {
+                        processor: self,
+                        game: &mut game,
+                        speed: &mut speed,
+                        search: &mut search,
+                        hooks: &mut hooks,
+                        cycles_per_frame,
+                        max_size,
+                    });
+                    redraw = true;
+                }
+                hooks.remote = Some(remote);
+            }
 
-            // emulate one cycle
-            self.cycle();
+            // emulate as many frames as the speed asks for, remember any screen update on the way
+            let frames = speed.frames();
+            let mut frame = 0;
+            while frame < frames || (speed.is_uncapped() && frame_start.elapsed() < frame_time) {
+                redraw |= hooks.run_frame(self, cycles_per_frame);
+                frame += 1;
+            }
+
+            // the speed goes first, script overlays show at normal speed
+            let new_status = speed.label().or_else(|| hooks.overlay());
+            if new_status != status {
+                status = new_status;
+                frontend.set_status(status.clone());
+                redraw = true;
+            }
 
-            // draw to screen using sdl2
-            if self.draw_flag {
-                display.draw(&self.screen);
+            // draw to screen, at most once per frame
+            if redraw || frontend.is_animated() {
+                frontend.draw(self);
             }
 
             // play sound using sdl2
             // TODO
 
             // modern pc's are too fast :o
-            thread::sleep(Duration::from_millis(3));
+            thread::sleep(frame_time.saturating_sub(frame_start.elapsed()));
+        }
+    }
+
+    // emulate a single 60Hz frame, true if the screen changed
+    pub fn run_frame(&mut self, cycles_per_frame: usize) -> bool {
+        let redraw = self.run(cycles_per_frame);
+        self.tick_timers();
+        redraw
+    }
+
+    // run a number of cycles, true if the screen changed on the way
+    pub fn run(&mut self, cycles: usize) -> bool {
+        let mut redraw = false;
+        let mut left = cycles;
+        while left > 0 {
+            // the jit runs whole blocks, as long as they fit into the cycles left
+            if let Some(jit) = &mut self.jit {
+                if !self.waiting_for_key && !self.waiting_for_frame {
+                    let ran = jit.run(
+                        &self.memory,
+                        self.pc,
+                        &mut self.register,
+                        &mut self.index,
+                        left,
+                    );
+                    if let Some(ran) = ran {
+                        self.pc += ran * OPCODE_SIZE;
+                        left -= ran;
+                        continue;
+                    }
+                }
+            }
+            self.cycle();
+            redraw |= self.draw_flag;
+            left -= 1;
+        }
+        redraw
+    }
+
+    // back to the state after power on, keeping the font and settings
+    pub fn reset(&mut self) {
+        let font_end = self.big_font_address + BIG_FONT_SIZE;
+        for (pos, byte) in self.memory.iter_mut().enumerate() {
+            if pos < self.font_address || pos >= font_end {
+                *byte = 0;
+            }
         }
+        self.register = [0; 16];
+        self.index = 0;
+        self.pc = self.load_address;
+        self.screen = [[0; crate::SCREEN_WIDTH]; crate::SCREEN_HEIGHT];
+        self.draw_flag = false;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.stack = [0; 16];
+        self.sp = 0;
+        self.key = [false; 16];
+        self.waiting_for_key = false;
+        self.waiting_key_location = 0;
+        self.waiting_key_pressed = None;
+        self.waiting_for_frame = false;
+        self.forget_code();
+    }
+
+    pub fn screen(&self) -> &[[u8; crate::SCREEN_WIDTH]; crate::SCREEN_HEIGHT] {
+        &self.screen
+    }
+
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.register
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn load_address(&self) -> usize {
+        self.load_address
+    }
+
+    pub fn timers(&self) -> (usize, usize) {
+        (self.delay_timer, self.sound_timer)
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    // fx0a or dxyn hold the program, the next cycle doesn't execute anything
+    pub fn is_waiting(&self) -> bool {
+        self.waiting_for_key || self.waiting_for_frame
+    }
+
+    // setters for debuggers and embedders, the caller keeps values in range
+    pub fn set_register(&mut self, x: usize, value: u8) {
+        self.register[x] = value;
+    }
+
+    pub fn set_index(&mut self, index: usize) {
+        self.index = index;
+    }
+
+    pub fn set_pc(&mut self, pc: usize) {
+        self.pc = pc;
+    }
+
+    pub fn set_timers(&mut self, delay_timer: usize, sound_timer: usize) {
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+    }
+
+    // everything a running game can change, settings aren't part of it
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = Writer::new(STATE_MAGIC, STATE_VERSION);
+        state.u32(self.memory.len());
+        state.bytes(&self.memory);
+        state.bytes(&self.register);
+        state.u32(self.index);
+        state.u32(self.pc);
+        for row in self.screen.iter() {
+            state.bytes(row);
+        }
+        state.u8(self.delay_timer as u8);
+        state.u8(self.sound_timer as u8);
+        for &address in self.stack.iter() {
+            state.u32(address);
+        }
+        state.u8(self.sp as u8);
+        for &pressed in self.key.iter() {
+            state.bool(pressed);
+        }
+        state.bool(self.waiting_for_key);
+        state.u8(self.waiting_key_location as u8);
+        state.u8(self.waiting_key_pressed.map_or(0xff, |key| key as u8));
+        state.bool(self.waiting_for_frame);
+        state.finish()
+    }
+
+    // restore a save state of the same platform, nothing changes on errors
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut state = Reader::new(state, STATE_MAGIC, STATE_VERSION)?;
+        let memory_size = state.u32()?;
+        if memory_size != self.memory.len() {
+            return Err(StateError::MemorySize {
+                state: memory_size,
+                processor: self.memory.len(),
+            });
+        }
+        let memory = state.bytes(memory_size)?;
+        let mut register = [0; 16];
+        register.copy_from_slice(state.bytes(16)?);
+        let index = state.u32()?;
+        let pc = state.u32()?;
+        let mut screen = [[0; crate::SCREEN_WIDTH]; crate::SCREEN_HEIGHT];
+        for row in screen.iter_mut() {
+            row.copy_from_slice(state.bytes(crate::SCREEN_WIDTH)?);
+        }
+        let delay_timer = state.u8()? as usize;
+        let sound_timer = state.u8()? as usize;
+        let mut stack = [0; 16];
+        for address in stack.iter_mut() {
+            *address = state.u32()?;
+        }
+        let sp = state.u8()? as usize;
+        let mut key = [false; 16];
+        for pressed in key.iter_mut() {
+            *pressed = state.bool()?;
+        }
+        let waiting_for_key = state.bool()?;
+        let waiting_key_location = state.u8()? as usize;
+        let waiting_key_pressed = match state.u8()? {
+            0xff => None,
+            key => Some(key as usize),
+        };
+        let waiting_for_frame = state.bool()?;
+
+        if pc + 1 >= memory_size {
+            return Err(StateError::Invalid("pc"));
+        }
+        if index >= memory_size {
+            return Err(StateError::Invalid("index"));
+        }
+        // the next call would have nowhere to go
+        if sp >= stack.len() || stack.iter().any(|&address| address >= memory_size) {
+            return Err(StateError::Invalid("stack"));
+        }
+        if waiting_key_location >= 16 || waiting_key_pressed.is_some_and(|key| key >= 16) {
+            return Err(StateError::Invalid("key"));
+        }
+
+        self.memory.copy_from_slice(memory);
+        self.register = register;
+        self.index = index;
+        self.pc = pc;
+        self.screen = screen;
+        self.draw_flag = true;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.stack = stack;
+        self.sp = sp;
+        self.key = key;
+        self.waiting_for_key = waiting_for_key;
+        self.waiting_key_location = waiting_key_location;
+        self.waiting_key_pressed = waiting_key_pressed;
+        self.waiting_for_frame = waiting_for_frame;
+        self.forget_code();
+        Ok(())
     }
 
     pub fn cycle(&mut self) {
         // reset
         self.draw_flag = false;
 
-        // opcode FX0A freezes the program, until a key is pressed
-        if self.waiting_for_key {
-            for (pos, &val) in self.key.iter().enumerate() {
-                if self.key[pos] {
-                    self.waiting_for_key = false;
-                    self.register[self.waiting_key_location] = val as u8;
-                    break;
+        // opcode FX0A freezes the program, until a key is pressed and released
+        // and DXYN might wait for the next frame
+        if !self.waiting_for_key && !self.waiting_for_frame {
+            // execute current opcode
+            match self.engine {
+                Engine::Interpreter => {
+                    let opcode = self.fetch_opcode();
+                    self.decode_opcode(opcode);
                 }
+                // single instructions of the jit engine run cached as well
+                Engine::Cached | Engine::Jit => self.run_cached(),
             }
-        } else {
-            // decr both timers every cycle
-            if self.delay_timer > 0 {
-                self.delay_timer -= 1;
+        }
+    }
+
+    // decode each address only once, then keep executing its instruction
+    fn run_cached(&mut self) {
+        // emptied whenever a lot of memory changed
+        if self.decoded.is_empty() {
+            self.decoded.resize(self.memory.len(), None);
+        }
+        let instruction = match self.decoded[self.pc] {
+            Some(instruction) => instruction,
+            None => {
+                let instruction = Instruction::decode(self.fetch_opcode());
+                self.decoded[self.pc] = Some(instruction);
+                instruction
             }
-            if self.sound_timer > 0 {
-                self.sound_timer -= 1;
+        };
+        self.execute(instruction);
+    }
+
+    // memory changed in bulk, so decode and compile everything again
+    fn forget_code(&mut self) {
+        self.decoded.clear();
+        if let Some(jit) = &mut self.jit {
+            jit.clear();
+        }
+    }
+
+    // scripts get the memory for their callbacks and give it back, without copying it
+    #[cfg(feature = "scripting")]
+    pub(crate) fn swap_memory(&mut self, memory: &mut Vec<u8>) {
+        std::mem::swap(&mut self.memory, memory);
+    }
+
+    // programs may modify themselves, so forget instructions overlapping written memory
+    pub fn write_memory(&mut self, address: usize, value: u8) {
+        self.memory[address] = value;
+        if let Some(jit) = &mut self.jit {
+            jit.invalidate(address);
+        }
+        if !self.decoded.is_empty() {
+            self.decoded[address] = None;
+            if address > 0 {
+                self.decoded[address - 1] = None;
             }
+        }
+    }
 
-            // execute current opcode
-            let opcode = self.fetch_opcode();
-            self.decode_opcode(opcode);
+    // decr both timers, once per frame (60Hz)
+    pub fn tick_timers(&mut self) {
+        self.waiting_for_frame = false;
+
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+    }
+
+    pub fn key_event(&mut self, event: KeyEvent) {
+        match event {
+            KeyEvent::Pressed(key) => {
+                self.key[key] = true;
+                // FX0A remembers the first key pressed while waiting...
+                if self.waiting_for_key && self.waiting_key_pressed.is_none() {
+                    self.waiting_key_pressed = Some(key);
+                }
+            }
+            KeyEvent::Released(key) => {
+                self.key[key] = false;
+                // ...and continues once that key is released again
+                if self.waiting_for_key && self.waiting_key_pressed == Some(key) {
+                    self.register[self.waiting_key_location] = key as u8;
+                    self.waiting_for_key = false;
+                    self.waiting_key_pressed = None;
+                }
+            }
         }
     }
 
     pub fn load_game(&mut self, game: &[u8]) {
         for (pos, &val) in game.iter().enumerate() {
-            let position = crate::GAME_ENTRY + pos;
-            if position < crate::MEMORY_SIZE {
+            let position = self.load_address + pos;
+            if position < self.memory.len() {
                 // don't go above mem limit
                 self.memory[position] = val;
             } else {
                 break;
             }
         }
+        self.forget_code();
     }
 
     pub fn fetch_opcode(&self) -> u16 {
@@ -125,68 +628,49 @@ impl Processor {
     }
 
     pub fn decode_opcode(&mut self, opcode: u16) {
-        // values from http://devernay.free.fr/hacks/chip8/C8TECH10.HTM#3.0
-
-        let nibbles = (
-            ((opcode & 0xF000) >> 12) as usize,
-            ((opcode & 0x0F00) >> 8) as usize,
-            ((opcode & 0x00F0) >> 4) as usize,
-            (opcode & 0x000F) as usize,
-        );
-
-        // nnn or addr - A 12-bit value, the lowest 12 bits of the instruction
-        let nnn = (opcode & 0x0FFF) as usize;
-
-        // n or nibble - A 4-bit value, the lowest 4 bits of the instruction
-        let n = nibbles.3 as usize;
-
-        // x - A 4-bit value, the lower 4 bits of the high byte of the instruction
-        let x = nibbles.1 as usize;
-
-        // y - A 4-bit value, the upper 4 bits of the low byte of the instruction
-        let y = nibbles.2 as usize;
-
-        //kk or byte - An 8-bit value, the lowest 8 bits of the instruction
-        let kk = (opcode & 0x00FF) as u8;
-
-        // match nibbles to opcodes => run funtion
-        match nibbles {
-            (0x00, 0x00, 0x0e, 0x00) => self.code_00e0(),
-            (0x00, 0x00, 0x0e, 0x0e) => self.code_00ee(),
-            (0x01, _, _, _) => self.code_1nnn(nnn),
-            (0x02, _, _, _) => self.code_2nnn(nnn),
-            (0x03, _, _, _) => self.code_3xkk(x, kk),
-            (0x04, _, _, _) => self.code_4xkk(x, kk),
-            (0x05, _, _, 0x00) => self.code_5xy0(x, y),
-            (0x06, _, _, _) => self.code_6xkk(x, kk),
-            (0x07, _, _, _) => self.code_7xkk(x, kk),
-            (0x08, _, _, 0x00) => self.code_8xy0(x, y),
-            (0x08, _, _, 0x01) => self.code_8xy1(x, y),
-            (0x08, _, _, 0x02) => self.code_8xy2(x, y),
-            (0x08, _, _, 0x03) => self.code_8xy3(x, y),
-            (0x08, _, _, 0x04) => self.code_8xy4(x, y),
-            (0x08, _, _, 0x05) => self.code_8xy5(x, y),
-            (0x08, _, _, 0x06) => self.code_8xy6(x, y),
-            (0x08, _, _, 0x07) => self.code_8xy7(x, y),
-            (0x08, _, _, 0x0e) => self.code_8xye(x, y),
-            (0x09, _, _, 0x00) => self.code_9xy0(x, y),
-            (0x0a, _, _, _) => self.code_annn(nnn),
-            (0x0b, _, _, _) => self.code_bnnn(nnn),
-            (0x0c, _, _, _) => self.code_cxkk(x, kk),
-            (0x0d, _, _, _) => self.code_dxyn(x, y, n),
-            (0x0e, _, 0x09, 0x0e) => self.code_ex9e(x),
-            (0x0e, _, 0x0a, 0x01) => self.code_exa1(x),
-            (0x0f, _, 0x00, 0x07) => self.code_fx07(x),
-            (0x0f, _, 0x00, 0x0a) => self.code_fx0a(x),
-            (0x0f, _, 0x01, 0x05) => self.code_fx15(x),
-            (0x0f, _, 0x01, 0x08) => self.code_fx18(x),
-            (0x0f, _, 0x01, 0x0e) => self.code_fx1e(x),
-            (0x0f, _, 0x02, 0x09) => self.code_fx29(x),
-            (0x0f, _, 0x03, 0x03) => self.code_fx33(x),
-            (0x0f, _, 0x05, 0x05) => self.code_fx55(x),
-            (0x0f, _, 0x06, 0x05) => self.code_fx65(x),
-            _ => self.pc += OPCODE_SIZE,
-        };
+        self.execute(Instruction::decode(opcode));
+    }
+
+    // pick the function of an instruction
+    fn execute(&mut self, instruction: Instruction) {
+        match instruction {
+            Instruction::Clear => self.code_00e0(),
+            Instruction::Return => self.code_00ee(),
+            Instruction::Jump(nnn) => self.code_1nnn(nnn),
+            Instruction::Call(nnn) => self.code_2nnn(nnn),
+            Instruction::SkipEqual(x, kk) => self.code_3xkk(x, kk),
+            Instruction::SkipNotEqual(x, kk) => self.code_4xkk(x, kk),
+            Instruction::SkipEqualRegister(x, y) => self.code_5xy0(x, y),
+            Instruction::Load(x, kk) => self.code_6xkk(x, kk),
+            Instruction::Add(x, kk) => self.code_7xkk(x, kk),
+            Instruction::Move(x, y) => self.code_8xy0(x, y),
+            Instruction::Or(x, y) => self.code_8xy1(x, y),
+            Instruction::And(x, y) => self.code_8xy2(x, y),
+            Instruction::Xor(x, y) => self.code_8xy3(x, y),
+            Instruction::AddRegister(x, y) => self.code_8xy4(x, y),
+            Instruction::Sub(x, y) => self.code_8xy5(x, y),
+            Instruction::ShiftRight(x, y) => self.code_8xy6(x, y),
+            Instruction::SubNegated(x, y) => self.code_8xy7(x, y),
+            Instruction::ShiftLeft(x, y) => self.code_8xye(x, y),
+            Instruction::SkipNotEqualRegister(x, y) => self.code_9xy0(x, y),
+            Instruction::LoadIndex(nnn) => self.code_annn(nnn),
+            Instruction::JumpOffset(nnn) => self.code_bnnn(nnn),
+            Instruction::Random(x, kk) => self.code_cxkk(x, kk),
+            Instruction::Draw(x, y, n) => self.code_dxyn(x, y, n),
+            Instruction::SkipPressed(x) => self.code_ex9e(x),
+            Instruction::SkipNotPressed(x) => self.code_exa1(x),
+            Instruction::LoadDelay(x) => self.code_fx07(x),
+            Instruction::WaitKey(x) => self.code_fx0a(x),
+            Instruction::SetDelay(x) => self.code_fx15(x),
+            Instruction::SetSound(x) => self.code_fx18(x),
+            Instruction::AddIndex(x) => self.code_fx1e(x),
+            Instruction::Font(x) => self.code_fx29(x),
+            Instruction::BigFont(x) => self.code_fx30(x),
+            Instruction::Bcd(x) => self.code_fx33(x),
+            Instruction::Store(x) => self.code_fx55(x),
+            Instruction::Restore(x) => self.code_fx65(x),
+            Instruction::Unknown => self.pc += OPCODE_SIZE,
+        }
     }
 
     // Clear screen
@@ -263,18 +747,27 @@ impl Processor {
     // Set Vx = Vx OR Vy
     fn code_8xy1(&mut self, x: usize, y: usize) {
         self.register[x] = self.register[x] | self.register[y];
+        if self.quirks.vf_reset {
+            self.register[0x0f] = 0;
+        }
         self.pc += OPCODE_SIZE;
     }
 
     // Set Vx = Vx AND Vy
     fn code_8xy2(&mut self, x: usize, y: usize) {
         self.register[x] = self.register[x] & self.register[y];
+        if self.quirks.vf_reset {
+            self.register[0x0f] = 0;
+        }
         self.pc += OPCODE_SIZE;
     }
 
     // Set Vx = Vx XOR Vy
     fn code_8xy3(&mut self, x: usize, y: usize) {
         self.register[x] = self.register[x] ^ self.register[y];
+        if self.quirks.vf_reset {
+            self.register[0x0f] = 0;
+        }
         self.pc += OPCODE_SIZE;
     }
 
@@ -297,10 +790,11 @@ impl Processor {
         self.pc += OPCODE_SIZE;
     }
 
-    // Set Vx = Vx SHR 1
-    fn code_8xy6(&mut self, x: usize, _y: usize) {
-        self.register[0x0f] = self.register[x] & 1; // set if least significant bit == 1
-        self.register[x] = self.register[x] / 2;
+    // Set Vx = Vx SHR 1 (or Vy SHR 1)
+    fn code_8xy6(&mut self, x: usize, y: usize) {
+        let source = if self.quirks.shift_in_place { x } else { y };
+        self.register[0x0f] = self.register[source] & 1; // set if least significant bit == 1
+        self.register[x] = self.register[source] / 2;
         self.pc += OPCODE_SIZE;
     }
 
@@ -311,10 +805,11 @@ impl Processor {
         self.pc += OPCODE_SIZE;
     }
 
-    // Set Vx = Vx SHL 1
-    fn code_8xye(&mut self, x: usize, _y: usize) {
-        self.register[0x0f] = (self.register[x] & 0b10000000) >> 7;
-        self.register[x] <<= 1;
+    // Set Vx = Vx SHL 1 (or Vy SHL 1)
+    fn code_8xye(&mut self, x: usize, y: usize) {
+        let source = if self.quirks.shift_in_place { x } else { y };
+        self.register[0x0f] = (self.register[source] & 0b10000000) >> 7;
+        self.register[x] = self.register[source] << 1;
         self.pc += OPCODE_SIZE;
     }
 
@@ -333,9 +828,10 @@ impl Processor {
         self.pc += OPCODE_SIZE;
     }
 
-    // Jump to location nnn + V0
+    // Jump to location nnn + V0 (or xnn + Vx)
     fn code_bnnn(&mut self, nnn: usize) {
-        self.pc = nnn + self.register[0x00] as usize;
+        let offset = if self.quirks.jump_vx { nnn >> 8 } else { 0 };
+        self.pc = nnn + self.register[offset] as usize;
     }
 
     // Set Vx = random byte AND kk
@@ -348,12 +844,23 @@ impl Processor {
     // Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
     fn code_dxyn(&mut self, x: usize, y: usize, n: usize) {
         self.register[0x0f] = 0;
+        // the sprite always starts on screen
+        let start_y = self.register[y] as usize % crate::SCREEN_HEIGHT;
+        let start_x = self.register[x] as usize % crate::SCREEN_WIDTH;
         for byte in 0..n {
             // get y coord, which we want to draw -> use modulo, so we don't overlap
-            let y = (self.register[y] as usize + byte) % crate::SCREEN_HEIGHT;
+            let y = start_y + byte;
+            if self.quirks.clip_sprites && y >= crate::SCREEN_HEIGHT {
+                break;
+            }
+            let y = y % crate::SCREEN_HEIGHT;
             for bit in 0..8 {
                 // get x coord, just as above
-                let x = (self.register[x] as usize + bit) % crate::SCREEN_WIDTH;
+                let x = start_x + bit;
+                if self.quirks.clip_sprites && x >= crate::SCREEN_WIDTH {
+                    break;
+                }
+                let x = x % crate::SCREEN_WIDTH;
                 // bit hack to get every bit in a row
                 let pixel_to_draw = (self.memory[self.index + byte] >> (7 - bit)) & 1;
                 // check if we will overwrite an existing pixel
@@ -362,6 +869,7 @@ impl Processor {
             }
         }
         self.draw_flag = true;
+        self.waiting_for_frame = self.quirks.display_wait;
         self.pc += OPCODE_SIZE;
     }
 
@@ -393,6 +901,7 @@ impl Processor {
     fn code_fx0a(&mut self, x: usize) {
         self.waiting_for_key = true;
         self.waiting_key_location = x; // safe for later
+        self.waiting_key_pressed = None;
         self.pc += OPCODE_SIZE;
     }
 
@@ -416,18 +925,25 @@ impl Processor {
 
     // Set I = location of sprite for digit Vx
     fn code_fx29(&mut self, x: usize) {
-        let sprite_name = self.register[x] as usize;
-        let mem_position = sprite_name * 5; // single sprite is 5byte
+        let sprite_name = self.register[x] as usize & 0x0f;
+        let mem_position = self.font_address + sprite_name * 5; // single sprite is 5byte
         self.index = mem_position;
         self.pc += OPCODE_SIZE;
     }
 
+    // Set I = location of the big sprite for digit Vx (SCHIP)
+    fn code_fx30(&mut self, x: usize) {
+        let sprite_name = self.register[x] as usize & 0x0f;
+        self.index = self.big_font_address + sprite_name * 10; // big sprites are 10byte
+        self.pc += OPCODE_SIZE;
+    }
+
     // Store BCD representation of Vx in memory locations I, I+1, and I+2
     fn code_fx33(&mut self, x: usize) {
         let val = self.register[x];
-        self.memory[self.index] = (val / 100) as u8;
-        self.memory[self.index + 1] = ((val % 100) / 10) as u8;
-        self.memory[self.index + 2] = (val % 10) as u8;
+        self.write_memory(self.index, val / 100);
+        self.write_memory(self.index + 1, (val % 100) / 10);
+        self.write_memory(self.index + 2, val % 10);
         self.pc += OPCODE_SIZE;
     }
 
@@ -435,7 +951,10 @@ impl Processor {
     fn code_fx55(&mut self, x: usize) {
         // TODO offset correct?
         for reg_i in 0..x + 1 {
-            self.memory[self.index + reg_i] = self.register[reg_i];
+            self.write_memory(self.index + reg_i, self.register[reg_i]);
+        }
+        if self.quirks.increment_index {
+            self.index += x + 1;
         }
         self.pc += OPCODE_SIZE;
     }
@@ -445,6 +964,9 @@ impl Processor {
         for reg_i in 0..x + 1 {
             self.register[reg_i] = self.memory[self.index + reg_i];
         }
+        if self.quirks.increment_index {
+            self.index += x + 1;
+        }
         self.pc += OPCODE_SIZE;
     }
 }
@@ -452,6 +974,8 @@ impl Processor {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fontset::Font;
+    use crate::platform::Platform;
 
     fn new_processor() -> Processor {
         let mut processor = Processor::new();
@@ -482,6 +1006,214 @@ mod tests {
         assert_eq!(processor.memory[crate::GAME_ENTRY + 2], 3);
     }
 
+    #[test]
+    fn test_reset() {
+        let mut processor = new_processor();
+        processor.load_game(&[0x60, 0x05]);
+        processor.cycle();
+        processor.delay_timer = 10;
+        processor.screen[0][0] = 1;
+
+        processor.reset();
+        assert_eq!(processor.pc, ENTRY);
+        assert_eq!(processor.register, [0; 16]);
+        assert_eq!(processor.delay_timer, 0);
+        assert_eq!(processor.screen[0][0], 0);
+        assert_eq!(processor.memory[ENTRY], 0);
+        // the font survives
+        assert_eq!(processor.memory[0..5], [0xF0, 0x90, 0x90, 0x90, 0xF0]);
+    }
+
+    // run a single instruction and compare everything it could have changed
+    fn run_both(opcode: u16) {
+        let mut processors: Vec<Processor> = [Engine::Interpreter, Engine::Cached]
+            .iter()
+            .map(|&engine| {
+                let mut processor = new_processor();
+                processor.set_engine(engine);
+                processor.index = 0x300;
+                processor.stack[0] = 0x300;
+                processor.sp = 1;
+                processor.key[3] = true;
+                processor.load_game(&opcode.to_be_bytes());
+                processor.cycle();
+                processor
+            })
+            .collect();
+        let cached = processors.pop().unwrap();
+        let interpreter = processors.pop().unwrap();
+
+        assert_eq!(interpreter.register, cached.register, "{:04x}", opcode);
+        assert_eq!(interpreter.index, cached.index, "{:04x}", opcode);
+        assert_eq!(interpreter.pc, cached.pc, "{:04x}", opcode);
+        assert_eq!(interpreter.sp, cached.sp, "{:04x}", opcode);
+        assert_eq!(interpreter.stack, cached.stack, "{:04x}", opcode);
+        assert_eq!(interpreter.timers(), cached.timers(), "{:04x}", opcode);
+        assert_eq!(interpreter.memory, cached.memory, "{:04x}", opcode);
+        assert_eq!(interpreter.screen, cached.screen, "{:04x}", opcode);
+        assert_eq!(
+            interpreter.waiting_for_key, cached.waiting_for_key,
+            "{:04x}",
+            opcode
+        );
+    }
+
+    #[test]
+    fn test_cached_engine() {
+        // everything but cxkk, which is random
+        for opcode in (0x0000..=0xffff).filter(|opcode| opcode & 0xf000 != 0xc000) {
+            run_both(opcode);
+        }
+    }
+
+    #[test]
+    fn test_self_modifying() {
+        let game = [
+            0x6a, 0x01, // VA = 1, gets overwritten
+            0xa2, 0x00, // I = 0x200
+            0x60, 0x6a, // V0 = 0x6a
+            0x61, 0x05, // V1 = 0x05
+            0xf1, 0x55, // store V0 and V1 at 0x200, so it becomes VA = 5
+            0x12, 0x00, // jump back
+        ];
+        for &engine in &[Engine::Cached, Engine::Jit] {
+            let mut processor = new_processor();
+            processor.set_engine(engine);
+            processor.load_game(&game);
+            processor.run(7);
+            assert_eq!(processor.register[0xa], 5, "{:?}", engine);
+        }
+    }
+
+    #[test]
+    fn test_jit_engine() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(8);
+        for round in 0..200 {
+            // random arithmetic, with skips and stores in between, looping forever
+            let mut game = Vec::new();
+            for _ in 0..24 {
+                let x = rng.gen_range(0..16u16) << 8;
+                let y = rng.gen_range(0..16u16) << 4;
+                let opcode = match rng.gen_range(0..8) {
+                    0 => 0x6000 | x | rng.gen_range(0..256u16),
+                    1 => 0x7000 | x | rng.gen_range(0..256u16),
+                    2 | 3 => 0x8000 | x | y | [0, 1, 2, 3, 4, 5, 6, 7, 0xe][rng.gen_range(0..9)],
+                    4 => 0x3000 | x | rng.gen_range(0..4u16),
+                    5 => 0xa300 | rng.gen_range(0..0x80u16),
+                    6 => 0xf01e | x,
+                    _ => [0xf033, 0xf055, 0xf065][rng.gen_range(0..3)] | x,
+                };
+                game.extend(opcode.to_be_bytes());
+            }
+            game.extend([0xa3, 0x00, 0x12, 0x00]);
+
+            let quirks = match round % 3 {
+                0 => Platform::Chip8.quirks(),
+                1 => Platform::Schip.quirks(),
+                _ => Quirks::default(),
+            };
+            let mut processors: Vec<Processor> = [Engine::Interpreter, Engine::Jit]
+                .iter()
+                .map(|&engine| {
+                    let mut processor = Processor::new();
+                    processor.set_quirks(quirks);
+                    processor.set_engine(engine);
+                    processor.load_game(&game);
+                    for _ in 0..20 {
+                        processor.run(37);
+                        processor.tick_timers();
+                    }
+                    processor
+                })
+                .collect();
+            let jit = processors.pop().unwrap();
+            let interpreter = processors.pop().unwrap();
+
+            assert_eq!(interpreter.register, jit.register, "round {}", round);
+            assert_eq!(interpreter.index, jit.index, "round {}", round);
+            assert_eq!(interpreter.pc, jit.pc, "round {}", round);
+            assert_eq!(interpreter.memory, jit.memory, "round {}", round);
+        }
+    }
+
+    #[test]
+    fn test_save_state() {
+        let mut processor = new_processor();
+        processor.load_game(&[0x60, 0x2a, 0xa3, 0x00, 0xf0, 0x33, 0x12, 0x06]);
+        processor.run(3);
+        processor.delay_timer = 7;
+        processor.screen[3][4] = 1;
+        let state = processor.save_state();
+
+        let mut restored = Processor::new();
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.register, processor.register);
+        assert_eq!(restored.pc, processor.pc);
+        assert_eq!(restored.index, 0x300);
+        assert_eq!(restored.memory, processor.memory);
+        assert_eq!(restored.screen, processor.screen);
+        assert_eq!(restored.timers(), (7, 0));
+        assert_eq!(restored.save_state(), state);
+
+        // only into a processor with the same memory size
+        restored.set_memory_size(65536);
+        assert!(matches!(
+            restored.load_state(&state),
+            Err(StateError::MemorySize { .. })
+        ));
+        assert_eq!(
+            processor.load_state(&state[..100]),
+            Err(StateError::Truncated)
+        );
+        assert_eq!(processor.load_state(b"nope"), Err(StateError::WrongMagic));
+    }
+
+    #[test]
+    fn test_load_state_out_of_range() {
+        let mut processor = new_processor();
+        let state = processor.save_state();
+        // offsets behind the magic, version, memory size, memory and registers
+        let index = 4 + 1 + 4 + crate::MEMORY_SIZE + 16;
+        let stack = index + 8 + crate::SCREEN_WIDTH * crate::SCREEN_HEIGHT + 2;
+        let sp = stack + 16 * 4;
+
+        let mut broken = state.clone();
+        broken[index + 1] = 0x10;
+        assert_eq!(
+            processor.load_state(&broken),
+            Err(StateError::Invalid("index"))
+        );
+        let mut broken = state.clone();
+        broken[stack + 4 * 15 + 2] = 0x01;
+        assert_eq!(
+            processor.load_state(&broken),
+            Err(StateError::Invalid("stack"))
+        );
+        let mut broken = state.clone();
+        broken[sp] = 16;
+        assert_eq!(
+            processor.load_state(&broken),
+            Err(StateError::Invalid("stack"))
+        );
+        processor.load_state(&state).unwrap();
+    }
+
+    #[test]
+    fn test_load_address() {
+        let mut processor = Processor::new();
+        processor.set_load_address(Platform::Eti660.load_address());
+        processor.load_game(&[0x12, 0x34]);
+        assert_eq!(processor.memory[0x600], 0x12);
+        assert_eq!(processor.fetch_opcode(), 0x1234);
+        assert_eq!(processor.max_rom_size(), 4096 - 0x600);
+
+        // a font behind the rom limits its size
+        processor.set_font(&FONT, &BIG_FONT, 0xe00);
+        assert_eq!(processor.max_rom_size(), 0x800);
+    }
+
     #[test]
     fn test_code_00e0() {
         let mut processor = Processor::new();
@@ -797,9 +1529,47 @@ mod tests {
     fn test_code_fx0a() {
         let mut processor = new_processor();
         processor.decode_opcode(0xf20a);
-        assert_eq!(processor.waiting_for_key, true);
-        // TODO: missing some checks here?
+        assert!(processor.waiting_for_key);
         assert_eq!(processor.pc, NEXT);
+
+        // a press alone doesn't continue
+        processor.key_event(KeyEvent::Pressed(0xb));
+        processor.cycle();
+        assert!(processor.waiting_for_key);
+        assert_eq!(processor.pc, NEXT);
+
+        // releasing another key doesn't continue either
+        processor.key_event(KeyEvent::Released(0x3));
+        assert!(processor.waiting_for_key);
+
+        // releasing the pressed key stores its index
+        processor.key_event(KeyEvent::Released(0xb));
+        assert!(!processor.waiting_for_key);
+        assert_eq!(processor.register[2], 0xb);
+    }
+
+    #[test]
+    fn test_fx0a_ignores_held_key() {
+        // a key held down before FX0A doesn't count when released
+        let mut processor = new_processor();
+        processor.key_event(KeyEvent::Pressed(0x4));
+        processor.decode_opcode(0xf20a);
+        processor.key_event(KeyEvent::Released(0x4));
+        assert!(processor.waiting_for_key);
+
+        processor.key_event(KeyEvent::Pressed(0x0));
+        processor.key_event(KeyEvent::Released(0x0));
+        assert!(!processor.waiting_for_key);
+        assert_eq!(processor.register[2], 0x0);
+    }
+
+    #[test]
+    fn test_key_event() {
+        let mut processor = new_processor();
+        processor.key_event(KeyEvent::Pressed(0xa));
+        assert!(processor.key[0xa]);
+        processor.key_event(KeyEvent::Released(0xa));
+        assert!(!processor.key[0xa]);
     }
 
     #[test]
@@ -820,6 +1590,20 @@ mod tests {
         assert_eq!(processor.pc, NEXT);
     }
 
+    #[test]
+    fn test_tick_timers() {
+        let mut processor = new_processor();
+        processor.delay_timer = 2;
+        processor.sound_timer = 1;
+        // cycles don't touch the timers
+        processor.cycle();
+        assert_eq!(processor.delay_timer, 2);
+        processor.tick_timers();
+        processor.tick_timers();
+        assert_eq!(processor.delay_timer, 0);
+        assert_eq!(processor.sound_timer, 0);
+    }
+
     #[test]
     fn test_code_fx1e() {
         let mut processor = new_processor();
@@ -839,6 +1623,28 @@ mod tests {
         assert_eq!(processor.pc, NEXT);
     }
 
+    #[test]
+    fn test_code_fx30() {
+        let mut processor = new_processor();
+        processor.register[5] = 9;
+        processor.decode_opcode(0xf530);
+        assert_eq!(processor.index, 80 + 90);
+        assert_eq!(processor.memory[processor.index], 0xFF);
+        assert_eq!(processor.pc, NEXT);
+    }
+
+    #[test]
+    fn test_font_address() {
+        let mut processor = new_processor();
+        processor.set_font(Font::Vip.data(), &BIG_FONT, 0x50);
+        processor.register[5] = 1;
+        processor.decode_opcode(0xf529);
+        assert_eq!(processor.index, 0x55);
+        assert_eq!(processor.memory[0x55], 0x60);
+        processor.decode_opcode(0xf530);
+        assert_eq!(processor.index, 0xa0 + 10);
+    }
+
     #[test]
     fn test_code_fx33() {
         let mut processor = new_processor();
@@ -877,4 +1683,79 @@ mod tests {
         }
         assert_eq!(processor.pc, NEXT);
     }
+
+    #[test]
+    fn test_quirk_vf_reset() {
+        let mut processor = new_processor();
+        processor.set_quirks(Platform::Chip8.quirks());
+        processor.decode_opcode(0x8011);
+        assert_eq!(processor.register[0x0f], 0);
+    }
+
+    #[test]
+    fn test_quirk_shift() {
+        // shift Vy into Vx
+        let mut processor = new_processor();
+        processor.set_quirks(Platform::Chip8.quirks());
+        processor.decode_opcode(0x8416);
+        assert_eq!(processor.register[0x0f], 1);
+        assert_eq!(processor.register[4], 0);
+
+        let mut processor = new_processor();
+        processor.set_quirks(Platform::Chip8.quirks());
+        processor.decode_opcode(0x804e);
+        assert_eq!(processor.register[0], 6);
+    }
+
+    #[test]
+    fn test_quirk_increment_index() {
+        let mut processor = new_processor();
+        processor.set_quirks(Platform::Chip8.quirks());
+        processor.index = 100;
+        processor.decode_opcode(0xf255);
+        assert_eq!(processor.index, 103);
+        processor.decode_opcode(0xf065);
+        assert_eq!(processor.index, 104);
+    }
+
+    #[test]
+    fn test_quirk_jump_vx() {
+        let mut processor = new_processor();
+        processor.set_quirks(Platform::Schip.quirks());
+        processor.register[2] = 2;
+        processor.decode_opcode(0xb211);
+        assert_eq!(processor.pc, 0x213);
+    }
+
+    #[test]
+    fn test_quirk_clip_sprites() {
+        let mut processor = new_processor();
+        processor.set_quirks(Platform::Chip8.quirks());
+        processor.index = 0;
+        processor.memory[0] = 0b11111111;
+        processor.memory[1] = 0b11111111;
+        processor.register[0] = 60;
+        processor.register[1] = 31;
+        processor.decode_opcode(0xd012);
+        // drawn up to the edges...
+        assert_eq!(processor.screen[31][63], 1);
+        // ...but nothing wrapped around
+        assert_eq!(processor.screen[31][0], 0);
+        assert_eq!(processor.screen[0][60], 0);
+    }
+
+    #[test]
+    fn test_quirk_display_wait() {
+        let mut processor = new_processor();
+        processor.set_quirks(Platform::Chip8.quirks());
+        processor.load_game(&[0xd0, 0x01, 0x60, 0x42]);
+        processor.cycle();
+        assert_eq!(processor.pc, NEXT);
+        // waits for the next frame
+        processor.cycle();
+        assert_eq!(processor.pc, NEXT);
+        processor.tick_timers();
+        processor.cycle();
+        assert_eq!(processor.register[0], 0x42);
+    }
 }
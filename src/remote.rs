@@ -0,0 +1,669 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use serde_json::{json, Value};
+
+use crate::cartridge::Cartridge;
+use crate::cheat::{self, Cheat, CheatList, Comparison, Search};
+use crate::input::KeyEvent;
+use crate::processor::{Hooks, Processor};
+use crate::speed::Speed;
+use crate::watch::Watcher;
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const FAILED: i64 = -32000; // valid call, which didn't work out, e.g. a missing rom
+
+// a minute at a time, the main loop stands still while stepping
+const MAX_STEP_FRAMES: usize = 3600;
+
+// A request waiting for the main loop, which answers it between two frames
+pub struct Call {
+    request: String,
+    reply: Sender<Option<String>>, // None for notifications
+}
+
+impl Call {
+    pub fn answer(self, control: &mut Control) {
+        let _ = self.reply.send(handle(&self.request, control));
+    }
+}
+
+// Takes JSON-RPC calls, one per line, from stdin or a unix socket
+pub struct Server {
+    calls: Receiver<Call>,
+    socket: Option<PathBuf>, // removed again on drop
+}
+
+impl Server {
+    // answers go to stdout, which the terminal frontend can't share
+    pub fn stdio() -> Self {
+        let (sender, calls) = mpsc::channel();
+        thread::spawn(move || serve(io::stdin().lock(), io::stdout(), &sender));
+        Server {
+            calls,
+            socket: None,
+        }
+    }
+
+    // any number of clients, each one gets its answers in order
+    #[cfg(unix)]
+    pub fn unix(path: &str) -> io::Result<Self> {
+        use std::os::unix::fs::FileTypeExt;
+        use std::os::unix::net::{UnixListener, UnixStream};
+
+        // a socket left behind by an earlier run, nobody answers on it anymore,
+        // anything else at the path makes binding fail
+        let is_socket =
+            fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket());
+        if is_socket {
+            if UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    "another emulator listens on the socket",
+                ));
+            }
+            fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        let (sender, calls) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    if let Ok(writer) = stream.try_clone() {
+                        serve(BufReader::new(stream), writer, &sender);
+                    }
+                });
+            }
+        });
+        Ok(Server {
+            calls,
+            socket: Some(PathBuf::from(path)),
+        })
+    }
+
+    // everything that came in since the last frame
+    pub fn poll(&self) -> Vec<Call> {
+        self.calls.try_iter().collect()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Some(socket) = &self.socket {
+            let _ = fs::remove_file(socket);
+        }
+    }
+}
+
+fn serve(reader: impl BufRead, mut writer: impl Write, calls: &Sender<Call>) {
+    for request in reader.lines() {
+        let request = match request {
+            Ok(request) if request.trim().is_empty() => continue,
+            Ok(request) => request,
+            Err(_) => return,
+        };
+        let (reply, answer) = mpsc::channel();
+        // the main loop is gone
+        if calls.send(Call { request, reply }).is_err() {
+            return;
+        }
+        match answer.recv() {
+            Ok(Some(response)) => {
+                if writeln!(writer, "{}", response)
+                    .and_then(|_| writer.flush())
+                    .is_err()
+                {
+                    return;
+                }
+            }
+            Ok(None) => {}
+            Err(_) => return,
+        }
+    }
+}
+
+// What calls act on, borrowed from the main loop
+pub struct Control<'a> {
+    pub processor: &'a mut Processor,
+    pub game: &'a mut Vec<u8>,
+    pub speed: &'a mut Speed,
+    pub search: &'a mut Option<Search>, // the ram search of search_start and search
+    pub hooks: &'a mut Hooks,           // frames run like in the main loop, with scripts and cheats
+    pub cycles_per_frame: usize,
+    pub max_size: usize, // of roms, memory behind the load address
+}
+
+struct Error(i64, String);
+
+fn invalid_params(message: &str) -> Error {
+    Error(INVALID_PARAMS, message.to_string())
+}
+
+// the response to a single request, None for notifications
+pub fn handle(request: &str, control: &mut Control) -> Option<String> {
+    let request: Value = match serde_json::from_str(request) {
+        Ok(request) => request,
+        Err(err) => {
+            return Some(response(
+                Value::Null,
+                Err(Error(PARSE_ERROR, err.to_string())),
+            ))
+        }
+    };
+    let id = request.get("id").cloned();
+    let result = match request.get("method").and_then(Value::as_str) {
+        // a rom crashing the processor fails the call instead of the emulator
+        Some(method) => panic::catch_unwind(AssertUnwindSafe(|| {
+            call(method, request.get("params"), control)
+        }))
+        .unwrap_or_else(|_| Err(Error(FAILED, "the rom crashed the processor".to_string()))),
+        None => Err(Error(INVALID_REQUEST, "method missing".to_string())),
+    };
+    id.map(|id| response(id, result))
+}
+
+fn response(id: Value, result: Result<Value, Error>) -> String {
+    let response = match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(Error(code, message)) => {
+            json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+        }
+    };
+    response.to_string()
+}
+
+fn call(method: &str, params: Option<&Value>, control: &mut Control) -> Result<Value, Error> {
+    let param = |name: &str| params.and_then(|params| params.get(name));
+    let number = |name: &str| {
+        param(name)
+            .and_then(Value::as_u64)
+            .map(|value| value as usize)
+    };
+    let key = || match number("key") {
+        Some(key) if key < 16 => Ok(key),
+        _ => Err(invalid_params("key has to be between 0 and 15")),
+    };
+    let memory_size = control.processor.memory().len();
+    let address = || match number("address") {
+        Some(address) if address < memory_size => Ok(address),
+        _ => Err(invalid_params("address outside of memory")),
+    };
+    let max_size = control.max_size;
+    let processor = &mut *control.processor;
+
+    let result = match method {
+        "load_rom" => {
+            let path = param("path")
+                .and_then(Value::as_str)
+                .ok_or_else(|| invalid_params("path missing"))?;
+            // stdin is either where the calls come from or would block the main loop
+            if path == "-" {
+                return Err(invalid_params("roms can't be loaded from stdin"));
+            }
+            let entry = param("entry").and_then(Value::as_str);
+            let load_address = processor.load_address();
+            let cartridge = Cartridge::new(path, entry)
+                .and_then(|mut cartridge| {
+                    cartridge.relocate(load_address)?;
+                    cartridge.validate(max_size).map(|_| cartridge)
+                })
+                .map_err(|err| Error(FAILED, format!("{:?}", err)))?;
+            // the cheats of the old rom make no sense for the new one
+            let cheats = match &control.hooks.cheat_directory {
+                Some(directory) => CheatList::load(directory, &cartridge.sha1())
+                    .map_err(|err| Error(FAILED, format!("{:?}", err)))?,
+                None => CheatList::new(),
+            };
+            control.hooks.cheats = cheats;
+            if control.hooks.watcher.is_some() {
+                control.hooks.watcher = Some(Watcher::new(path, entry, load_address, max_size));
+            }
+            *control.game = cartridge.data().to_vec();
+            processor.reset();
+            processor.load_game(control.game);
+            control.hooks.game_loaded(processor);
+            Value::Null
+        }
+        "reset" => {
+            processor.reset();
+            processor.load_game(control.game);
+            control.hooks.game_loaded(processor);
+            Value::Null
+        }
+        "pause" => {
+            control.speed.set_paused(true);
+            Value::Null
+        }
+        "resume" => {
+            control.speed.set_paused(false);
+            Value::Null
+        }
+        // also while paused, true if the screen changed
+        "step" => {
+            let frames = match number("frames").unwrap_or(1) {
+                frames if frames <= MAX_STEP_FRAMES => frames,
+                _ => {
+                    return Err(invalid_params(&format!(
+                        "frames has to be at most {}",
+                        MAX_STEP_FRAMES
+                    )))
+                }
+            };
+            let mut redraw = false;
+            for _ in 0..frames {
+                redraw |= control.hooks.run_frame(processor, control.cycles_per_frame);
+            }
+            json!(redraw)
+        }
+        "press" => {
+            processor.key_event(KeyEvent::Pressed(key()?));
+            Value::Null
+        }
+        "release" => {
+            processor.key_event(KeyEvent::Released(key()?));
+            Value::Null
+        }
+        "read_memory" => {
+            let address = address()?;
+            let length = number("length").unwrap_or(1);
+            let memory = processor.memory();
+            match address.checked_add(length) {
+                Some(end) if end <= memory.len() => json!(memory[address..end]),
+                _ => return Err(invalid_params("range outside of memory")),
+            }
+        }
+        "write_memory" => {
+            let address = address()?;
+            let data: Vec<u8> = param("data")
+                .and_then(|data| serde_json::from_value(data.clone()).ok())
+                .ok_or_else(|| invalid_params("data has to be a list of bytes"))?;
+            if address.saturating_add(data.len()) > processor.memory().len() {
+                return Err(invalid_params("range outside of memory"));
+            }
+            for (pos, &value) in data.iter().enumerate() {
+                processor.write_memory(address + pos, value);
+            }
+            Value::Null
+        }
+        "registers" => {
+            let (delay_timer, sound_timer) = processor.timers();
+            json!({
+                "v": processor.registers(),
+                "i": processor.index(),
+                "pc": processor.pc(),
+                "delay_timer": delay_timer,
+                "sound_timer": sound_timer,
+            })
+        }
+        // a row per string, one hex digit per pixel with a bit for each plane
+        "screenshot" => {
+            let rows: Vec<String> = processor
+                .screen()
+                .iter()
+                .map(|row| row.iter().map(|&pixel| format!("{:x}", pixel)).collect())
+                .collect();
+            json!({"width": crate::SCREEN_WIDTH, "height": crate::SCREEN_HEIGHT, "rows": rows})
+        }
+        // how many addresses are left
+        "search_start" => {
+            let search = Search::new(processor.memory());
+            let count = search.candidates().len();
+            *control.search = Some(search);
+            json!(count)
+        }
+        "search" => {
+            let name = param("comparison")
+                .and_then(Value::as_str)
+                .ok_or_else(|| invalid_params("comparison missing"))?;
+            let value = match param("value").map(Value::as_u64) {
+                None if name == "equal" => return Err(invalid_params("equal needs a value")),
+                None => 0,
+                Some(Some(value)) if value < 256 => value as u8,
+                Some(_) => return Err(invalid_params("value has to be a byte")),
+            };
+            let comparison = Comparison::from_name(name, value).ok_or_else(|| {
+                invalid_params(&format!("comparison has to be one of {:?}", cheat::NAMES))
+            })?;
+            match control.search {
+                Some(search) => json!(search.filter(processor.memory(), comparison)),
+                None => return Err(Error(FAILED, "search_start has to come first".to_string())),
+            }
+        }
+        "search_results" => match control.search {
+            Some(search) => json!(search.candidates()),
+            None => json!([]),
+        },
+        "freeze" => {
+            let address = address()?;
+            let value = match number("value") {
+                Some(value) if value < 256 => value as u8,
+                _ => return Err(invalid_params("value has to be a byte")),
+            };
+            control.hooks.cheats.add(Cheat { address, value }, "");
+            Value::Null
+        }
+        "unfreeze" => {
+            control.hooks.cheats.remove(address()?);
+            Value::Null
+        }
+        "save_state" => json!(to_hex(&processor.save_state())),
+        "load_state" => {
+            let state = param("state")
+                .and_then(Value::as_str)
+                .and_then(from_hex)
+                .ok_or_else(|| invalid_params("state has to be a hex string"))?;
+            processor
+                .load_state(&state)
+                .map_err(|err| Error(FAILED, format!("invalid state: {:?}", err)))?;
+            Value::Null
+        }
+        _ => {
+            return Err(Error(
+                METHOD_NOT_FOUND,
+                format!("unknown method {}", method),
+            ))
+        }
+    };
+    Ok(result)
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|pos| u8::from_str_radix(text.get(pos..pos + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // V0 += 1, store it at 0x300 and wait for a key
+    const ROM: [u8; 8] = [0x70, 0x01, 0xa3, 0x00, 0xf0, 0x55, 0xf1, 0x0a];
+
+    fn request(processor: &mut Processor, speed: &mut Speed, request: &str) -> Value {
+        let mut game = ROM.to_vec();
+        let mut control = Control {
+            processor,
+            game: &mut game,
+            speed,
+            search: &mut None,
+            hooks: &mut Hooks::default(),
+            cycles_per_frame: 10,
+            max_size: 3584,
+        };
+        match handle(request, &mut control) {
+            Some(response) => serde_json::from_str(&response).unwrap(),
+            None => Value::Null,
+        }
+    }
+
+    #[test]
+    fn test_calls() {
+        let mut processor = Processor::new();
+        let mut speed = Speed::new();
+        processor.load_game(&ROM);
+        let mut call = |text: &str| request(&mut processor, &mut speed, text);
+
+        let state = call(r#"{"jsonrpc": "2.0", "id": 1, "method": "save_state"}"#);
+        let response =
+            call(r#"{"jsonrpc": "2.0", "id": 2, "method": "step", "params": {"frames": 2}}"#);
+        assert_eq!(
+            response,
+            json!({"jsonrpc": "2.0", "id": 2, "result": false})
+        );
+        let response = call(
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "read_memory", "params": {"address": 768, "length": 2}}"#,
+        );
+        assert_eq!(response["result"], json!([1, 0]));
+        let response = call(r#"{"jsonrpc": "2.0", "id": 4, "method": "registers"}"#);
+        assert_eq!(response["result"]["pc"], json!(0x208));
+
+        // notifications get no answer
+        assert_eq!(
+            call(r#"{"jsonrpc": "2.0", "method": "press", "params": {"key": 3}}"#),
+            Value::Null
+        );
+        call(r#"{"jsonrpc": "2.0", "method": "release", "params": {"key": 3}}"#);
+        let response = call(r#"{"jsonrpc": "2.0", "id": 5, "method": "registers"}"#);
+        assert_eq!(response["result"]["v"][1], json!(3));
+
+        let load = json!({"jsonrpc": "2.0", "id": 6, "method": "load_state", "params": {"state": state["result"]}});
+        assert_eq!(call(&load.to_string())["result"], Value::Null);
+        let response = call(
+            r#"{"jsonrpc": "2.0", "id": 7, "method": "read_memory", "params": {"address": 768}}"#,
+        );
+        assert_eq!(response["result"], json!([0]));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut processor = Processor::new();
+        let mut speed = Speed::new();
+        let mut code =
+            |text: &str| request(&mut processor, &mut speed, text)["error"]["code"].clone();
+
+        assert_eq!(code("{"), json!(PARSE_ERROR));
+        assert_eq!(
+            code(r#"{"jsonrpc": "2.0", "id": 1}"#),
+            json!(INVALID_REQUEST)
+        );
+        assert_eq!(
+            code(r#"{"jsonrpc": "2.0", "id": 1, "method": "fly"}"#),
+            json!(METHOD_NOT_FOUND)
+        );
+        assert_eq!(
+            code(r#"{"jsonrpc": "2.0", "id": 1, "method": "press", "params": {"key": 16}}"#),
+            json!(INVALID_PARAMS)
+        );
+        assert_eq!(
+            code(
+                r#"{"jsonrpc": "2.0", "id": 1, "method": "load_rom", "params": {"path": "/nonexistent"}}"#
+            ),
+            json!(FAILED)
+        );
+        assert_eq!(
+            code(
+                r#"{"jsonrpc": "2.0", "id": 1, "method": "load_state", "params": {"state": "abc"}}"#
+            ),
+            json!(INVALID_PARAMS)
+        );
+        assert_eq!(
+            code(r#"{"jsonrpc": "2.0", "id": 1, "method": "load_rom", "params": {"path": "-"}}"#),
+            json!(INVALID_PARAMS)
+        );
+        assert_eq!(
+            code(r#"{"jsonrpc": "2.0", "id": 1, "method": "step", "params": {"frames": 100000}}"#),
+            json!(INVALID_PARAMS)
+        );
+        assert_eq!(
+            code(
+                r#"{"jsonrpc": "2.0", "id": 1, "method": "read_memory", "params": {"length": 2}}"#
+            ),
+            json!(INVALID_PARAMS)
+        );
+
+        // i := 0xfff, then v0 and v1 are read from behind the end of memory
+        processor.load_game(&[0xaf, 0xff, 0xf1, 0x65]);
+        let response = request(
+            &mut processor,
+            &mut speed,
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "step"}"#,
+        );
+        assert_eq!(response["error"]["code"], json!(FAILED));
+    }
+
+    #[test]
+    fn test_search() {
+        let mut processor = Processor::new();
+        processor.load_game(&ROM);
+        let mut search = None;
+        let mut hooks = Hooks::default();
+        let mut control = Control {
+            processor: &mut processor,
+            game: &mut ROM.to_vec(),
+            speed: &mut Speed::new(),
+            search: &mut search,
+            hooks: &mut hooks,
+            cycles_per_frame: 10,
+            max_size: 3584,
+        };
+        let mut call = |text: &str| -> Value {
+            serde_json::from_str(&handle(text, &mut control).unwrap()).unwrap()
+        };
+
+        let response = call(r#"{"jsonrpc": "2.0", "id": 1, "method": "search_start"}"#);
+        assert_eq!(response["result"], json!(4096));
+        call(r#"{"jsonrpc": "2.0", "id": 2, "method": "step"}"#);
+        let response = call(
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "search", "params": {"comparison": "increased"}}"#,
+        );
+        assert_eq!(response["result"], json!(1));
+        let response = call(r#"{"jsonrpc": "2.0", "id": 4, "method": "search_results"}"#);
+        assert_eq!(response["result"], json!([0x300]));
+        let response = call(
+            r#"{"jsonrpc": "2.0", "id": 5, "method": "search", "params": {"comparison": "equal"}}"#,
+        );
+        assert_eq!(response["error"]["code"], json!(INVALID_PARAMS));
+
+        // frozen values get written after every frame
+        call(
+            r#"{"jsonrpc": "2.0", "id": 6, "method": "freeze", "params": {"address": 768, "value": 42}}"#,
+        );
+        call(r#"{"jsonrpc": "2.0", "id": 7, "method": "step"}"#);
+        let response = call(
+            r#"{"jsonrpc": "2.0", "id": 8, "method": "read_memory", "params": {"address": 768}}"#,
+        );
+        assert_eq!(response["result"], json!([42]));
+    }
+
+    #[test]
+    fn test_load_rom_cheats() {
+        let directory =
+            std::env::temp_dir().join(format!("chip8-rs-cheats-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let rom = directory.join("counter.ch8");
+        fs::write(&rom, ROM).unwrap();
+        let sha1 = Cartridge::new(rom.to_str().unwrap(), None).unwrap().sha1();
+        fs::write(directory.join(format!("{}.cht", sha1)), "300:2a answer").unwrap();
+
+        let mut processor = Processor::new();
+        let mut hooks = Hooks {
+            cheat_directory: Some(directory.to_str().unwrap().to_string()),
+            ..Hooks::default()
+        };
+        // a freeze of the rom before
+        hooks.cheats.add(Cheat::parse("301:01").unwrap(), "");
+        let mut control = Control {
+            processor: &mut processor,
+            game: &mut Vec::new(),
+            speed: &mut Speed::new(),
+            search: &mut None,
+            hooks: &mut hooks,
+            cycles_per_frame: 10,
+            max_size: 3584,
+        };
+        let load =
+            json!({"jsonrpc": "2.0", "id": 1, "method": "load_rom", "params": {"path": rom}});
+        assert!(handle(&load.to_string(), &mut control)
+            .unwrap()
+            .contains("result"));
+        handle(r#"{"jsonrpc": "2.0", "method": "step"}"#, &mut control);
+        assert_eq!(processor.memory()[0x300], 0x2a);
+        assert_eq!(processor.memory()[0x301], 0);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_pause() {
+        let mut processor = Processor::new();
+        let mut speed = Speed::new();
+        request(
+            &mut processor,
+            &mut speed,
+            r#"{"jsonrpc": "2.0", "method": "pause"}"#,
+        );
+        assert_eq!(speed.frames(), 0);
+        request(
+            &mut processor,
+            &mut speed,
+            r#"{"jsonrpc": "2.0", "method": "resume"}"#,
+        );
+        assert_eq!(speed.frames(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket() {
+        use std::os::unix::net::UnixStream;
+
+        let path = std::env::temp_dir().join(format!("chip8-rs-test-{}.sock", std::process::id()));
+        let path = path.to_str().unwrap();
+        let server = Server::unix(path).unwrap();
+        let mut stream = UnixStream::connect(path).unwrap();
+        writeln!(
+            stream,
+            r#"{{"jsonrpc": "2.0", "id": 1, "method": "registers"}}"#
+        )
+        .unwrap();
+
+        // the main loop comes by every frame
+        let mut processor = Processor::new();
+        let mut speed = Speed::new();
+        let mut game = Vec::new();
+        loop {
+            let calls = server.poll();
+            if calls.is_empty() {
+                thread::sleep(std::time::Duration::from_millis(10));
+                continue;
+            }
+            for call in calls {
+                call.answer(&mut Control {
+                    processor: &mut processor,
+                    game: &mut game,
+                    speed: &mut speed,
+                    search: &mut None,
+                    hooks: &mut Hooks::default(),
+                    cycles_per_frame: 10,
+                    max_size: 3584,
+                });
+            }
+            break;
+        }
+
+        let mut response = String::new();
+        BufReader::new(&stream).read_line(&mut response).unwrap();
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["result"]["pc"], json!(0x200));
+
+        // only while the emulator runs
+        drop(server);
+        assert!(!std::path::Path::new(path).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket_keeps_files() {
+        let path = std::env::temp_dir().join(format!("chip8-rs-test-{}.txt", std::process::id()));
+        fs::write(&path, "not a socket").unwrap();
+        assert!(Server::unix(path.to_str().unwrap()).is_err());
+        assert!(path.exists());
+        fs::remove_file(&path).unwrap();
+    }
+}
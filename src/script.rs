@@ -0,0 +1,535 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::rc::Rc;
+
+use rhai::{Array, CallFnOptions, Dynamic, EvalAltResult, FuncArgs, Map, Scope, AST, INT};
+
+use crate::cheat::{self, Cheat, CheatList, Comparison, Search};
+use crate::input::KeyEvent;
+use crate::processor::Processor;
+
+// Rhai scripts hooked into the emulation. The top level runs once at the start, before
+// the rom is in memory, then these functions get called, if the script defines them:
+//
+//   on_load()                 after the rom got loaded, again on every reset or reload
+//   on_frame()                after every frame
+//   on_pc(pc)                 before executing an address given to break_at
+//   on_write(address, value)  after an address given to watch changed
+//   on_key(key, pressed)      after the player pressed or released a key
+//
+// Functions can't see variables of the top level, so they share state through
+// the `this` map, e.g. this.count = (this.count ?? 0) + 1.
+//
+// Scripts read the processor with peek(address), reg(x), pc(), index() and frame(),
+// change it with poke(address, value), set_reg(x, value), press(key) and release(key),
+// and show(text) puts text on the screen, show("") removes it again. That line of text
+// is all a script can draw, the pixels of the screen belong to the rom.
+//
+// search_start() begins a ram search over all of memory, search(comparison) or
+// search("equal", value) keeps the matching addresses and returns how many are left,
+// results() lists them. freeze(address, value) keeps a byte at a value, unfreeze(address)
+// lets it go again.
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Io(io::Error),
+    Parse(rhai::ParseError),
+    Eval(Box<EvalAltResult>),
+}
+
+// changes asked for by a script, applied to the processor once it returns
+enum Command {
+    Poke(usize, u8), // already in memory, the processor still has to forget code decoded from it
+    SetRegister(usize, u8),
+    Key(KeyEvent),
+}
+
+// what the script functions see and leave behind, the processor isn't reachable from them
+#[derive(Default)]
+struct Shared {
+    memory: Vec<u8>, // the one of the processor, lent to the script while it runs
+    register: [u8; 16],
+    pc: usize,
+    index: usize,
+    frame: usize,
+    commands: Vec<Command>,
+    overlay: Option<String>,
+    breakpoints: BTreeSet<usize>,
+    watches: BTreeSet<usize>,
+    search: Option<Search>,
+    frozen: CheatList,
+}
+
+impl Shared {
+    fn search(&mut self, comparison: Comparison) -> Result<INT, Box<EvalAltResult>> {
+        match &mut self.search {
+            Some(search) => Ok(search.filter(&self.memory, comparison) as INT),
+            None => Err("search_start() has to come first".into()),
+        }
+    }
+
+    fn address(&self, address: INT) -> Result<usize, Box<EvalAltResult>> {
+        match usize::try_from(address) {
+            Ok(address) if address < self.memory.len() => Ok(address),
+            _ => Err(format!("address {:#x} outside of memory", address).into()),
+        }
+    }
+}
+
+fn byte(value: INT) -> Result<u8, Box<EvalAltResult>> {
+    u8::try_from(value).map_err(|_| format!("value {} doesn't fit into a byte", value).into())
+}
+
+fn nibble(value: INT, what: &str) -> Result<usize, Box<EvalAltResult>> {
+    match usize::try_from(value) {
+        Ok(value) if value < 16 => Ok(value),
+        _ => Err(format!("{} {} has to be between 0 and 15", what, value).into()),
+    }
+}
+
+pub struct Script {
+    engine: rhai::Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    this: Dynamic,
+    shared: Rc<RefCell<Shared>>,
+    failed: bool, // stops calling the script after its first error
+    watched: Vec<(usize, u8)>,
+}
+
+impl Script {
+    pub fn from_file(filename: &str, processor: &mut Processor) -> Result<Self, ScriptError> {
+        let source = fs::read_to_string(filename).map_err(ScriptError::Io)?;
+        Self::new(&source, processor)
+    }
+
+    // runs the top level right away, it sees the processor as it is now
+    pub fn new(source: &str, processor: &mut Processor) -> Result<Self, ScriptError> {
+        let shared = Rc::new(RefCell::new(Shared::default()));
+        let engine = new_engine(&shared);
+        let ast = engine.compile(source).map_err(ScriptError::Parse)?;
+
+        let mut script = Script {
+            engine,
+            ast,
+            scope: Scope::new(),
+            this: Dynamic::from_map(Map::new()),
+            shared,
+            failed: false,
+            watched: Vec::new(),
+        };
+        script.lend(processor);
+        let result = script
+            .engine
+            .run_ast_with_scope(&mut script.scope, &script.ast);
+        script.take_back(processor);
+        result.map_err(ScriptError::Eval)?;
+        Ok(script)
+    }
+
+    // call after the rom got loaded into memory
+    pub fn load(&mut self, processor: &mut Processor) {
+        self.call(processor, "on_load", ());
+    }
+
+    // text the script wants on the screen
+    pub fn overlay(&self) -> Option<String> {
+        self.shared.borrow().overlay.clone()
+    }
+
+    // like Processor::run_frame, stepping single cycles while pc or write hooks are set
+    pub fn run_frame(&mut self, processor: &mut Processor, cycles_per_frame: usize) -> bool {
+        let redraw = if self.has_hooks() {
+            let mut redraw = false;
+            for _ in 0..cycles_per_frame {
+                redraw |= self.cycle(processor);
+            }
+            redraw
+        } else {
+            processor.run(cycles_per_frame)
+        };
+        processor.tick_timers();
+        self.shared.borrow().frozen.apply(processor);
+
+        self.shared.borrow_mut().frame += 1;
+        self.call(processor, "on_frame", ());
+        redraw
+    }
+
+    // call after the processor saw the event
+    pub fn key_event(&mut self, processor: &mut Processor, event: KeyEvent) {
+        let (key, pressed) = match event {
+            KeyEvent::Pressed(key) => (key, true),
+            KeyEvent::Released(key) => (key, false),
+        };
+        self.call(processor, "on_key", (key as INT, pressed));
+    }
+
+    fn has_hooks(&self) -> bool {
+        if self.failed {
+            return false;
+        }
+        let shared = self.shared.borrow();
+        (!shared.breakpoints.is_empty() && self.defines("on_pc"))
+            || (!shared.watches.is_empty() && self.defines("on_write"))
+    }
+
+    fn cycle(&mut self, processor: &mut Processor) -> bool {
+        let pc = processor.pc();
+        let hit = !processor.is_waiting() && self.shared.borrow().breakpoints.contains(&pc);
+        if hit {
+            self.call(processor, "on_pc", (pc as INT,));
+        }
+
+        self.watched.clear();
+        for &address in self.shared.borrow().watches.iter() {
+            if let Some(&value) = processor.memory().get(address) {
+                self.watched.push((address, value));
+            }
+        }
+        let redraw = processor.run(1);
+
+        // the list is taken, as on_write may watch more addresses
+        let watched = std::mem::take(&mut self.watched);
+        for &(address, before) in watched.iter() {
+            let value = processor.memory()[address];
+            if value != before {
+                self.call(processor, "on_write", (address as INT, value as INT));
+            }
+        }
+        self.watched = watched;
+        redraw
+    }
+
+    fn defines(&self, name: &str) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|function| function.name == name)
+    }
+
+    fn call(&mut self, processor: &mut Processor, name: &str, args: impl FuncArgs) {
+        if self.failed || !self.defines(name) {
+            return;
+        }
+        self.lend(processor);
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.this);
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut self.scope,
+            &self.ast,
+            name,
+            args,
+        );
+        if let Err(err) = result {
+            eprintln!("script stopped in {}: {}", name, err);
+            self.failed = true;
+        }
+        self.take_back(processor);
+    }
+
+    // peeks read the memory of the processor itself instead of a copy, nothing runs meanwhile
+    fn lend(&self, processor: &mut Processor) {
+        let mut shared = self.shared.borrow_mut();
+        processor.swap_memory(&mut shared.memory);
+        shared.register = *processor.registers();
+        shared.pc = processor.pc();
+        shared.index = processor.index();
+    }
+
+    fn take_back(&self, processor: &mut Processor) {
+        let commands = {
+            let mut shared = self.shared.borrow_mut();
+            processor.swap_memory(&mut shared.memory);
+            std::mem::take(&mut shared.commands)
+        };
+        for command in commands {
+            match command {
+                Command::Poke(address, value) => processor.write_memory(address, value),
+                Command::SetRegister(x, value) => processor.set_register(x, value),
+                Command::Key(event) => processor.key_event(event),
+            }
+        }
+    }
+}
+
+fn new_engine(shared: &Rc<RefCell<Shared>>) -> rhai::Engine {
+    let mut engine = rhai::Engine::new();
+
+    let s = shared.clone();
+    engine.register_fn(
+        "peek",
+        move |address: INT| -> Result<INT, Box<EvalAltResult>> {
+            let shared = s.borrow();
+            let address = shared.address(address)?;
+            Ok(shared.memory[address] as INT)
+        },
+    );
+    let s = shared.clone();
+    engine.register_fn(
+        "poke",
+        move |address: INT, value: INT| -> Result<(), Box<EvalAltResult>> {
+            let mut shared = s.borrow_mut();
+            let address = shared.address(address)?;
+            let value = byte(value)?;
+            // later peeks see the new value already
+            shared.memory[address] = value;
+            shared.commands.push(Command::Poke(address, value));
+            Ok(())
+        },
+    );
+    let s = shared.clone();
+    engine.register_fn("reg", move |x: INT| -> Result<INT, Box<EvalAltResult>> {
+        Ok(s.borrow().register[nibble(x, "register")?] as INT)
+    });
+    let s = shared.clone();
+    engine.register_fn(
+        "set_reg",
+        move |x: INT, value: INT| -> Result<(), Box<EvalAltResult>> {
+            let mut shared = s.borrow_mut();
+            let x = nibble(x, "register")?;
+            let value = byte(value)?;
+            shared.register[x] = value;
+            shared.commands.push(Command::SetRegister(x, value));
+            Ok(())
+        },
+    );
+    let s = shared.clone();
+    engine.register_fn("pc", move || s.borrow().pc as INT);
+    let s = shared.clone();
+    engine.register_fn("index", move || s.borrow().index as INT);
+    let s = shared.clone();
+    engine.register_fn("frame", move || s.borrow().frame as INT);
+
+    let s = shared.clone();
+    engine.register_fn("press", move |key: INT| -> Result<(), Box<EvalAltResult>> {
+        let key = nibble(key, "key")?;
+        s.borrow_mut()
+            .commands
+            .push(Command::Key(KeyEvent::Pressed(key)));
+        Ok(())
+    });
+    let s = shared.clone();
+    engine.register_fn(
+        "release",
+        move |key: INT| -> Result<(), Box<EvalAltResult>> {
+            let key = nibble(key, "key")?;
+            s.borrow_mut()
+                .commands
+                .push(Command::Key(KeyEvent::Released(key)));
+            Ok(())
+        },
+    );
+    let s = shared.clone();
+    engine.register_fn("show", move |text: &str| {
+        s.borrow_mut().overlay = Some(text.to_string()).filter(|text| !text.is_empty());
+    });
+
+    let s = shared.clone();
+    engine.register_fn(
+        "break_at",
+        move |address: INT| -> Result<(), Box<EvalAltResult>> {
+            let mut shared = s.borrow_mut();
+            let address = shared.address(address)?;
+            shared.breakpoints.insert(address);
+            Ok(())
+        },
+    );
+    let s = shared.clone();
+    engine.register_fn(
+        "watch",
+        move |address: INT| -> Result<(), Box<EvalAltResult>> {
+            let mut shared = s.borrow_mut();
+            let address = shared.address(address)?;
+            shared.watches.insert(address);
+            Ok(())
+        },
+    );
+
+    let s = shared.clone();
+    engine.register_fn("search_start", move || {
+        let mut shared = s.borrow_mut();
+        let search = Search::new(&shared.memory);
+        let count = search.candidates().len() as INT;
+        shared.search = Some(search);
+        count
+    });
+    let s = shared.clone();
+    engine.register_fn(
+        "search",
+        move |name: &str| -> Result<INT, Box<EvalAltResult>> {
+            match Comparison::from_name(name, 0) {
+                Some(Comparison::Equal(_)) => {
+                    Err("equal needs a value, search(\"equal\", 3)".into())
+                }
+                Some(comparison) => s.borrow_mut().search(comparison),
+                None => Err(format!("comparison has to be one of {:?}", cheat::NAMES).into()),
+            }
+        },
+    );
+    let s = shared.clone();
+    engine.register_fn(
+        "search",
+        move |name: &str, value: INT| -> Result<INT, Box<EvalAltResult>> {
+            match Comparison::from_name(name, byte(value)?) {
+                Some(comparison) => s.borrow_mut().search(comparison),
+                None => Err(format!("comparison has to be one of {:?}", cheat::NAMES).into()),
+            }
+        },
+    );
+    let s = shared.clone();
+    engine.register_fn("results", move || -> Array {
+        match &s.borrow().search {
+            Some(search) => search
+                .candidates()
+                .iter()
+                .map(|&address| Dynamic::from(address as INT))
+                .collect(),
+            None => Array::new(),
+        }
+    });
+    let s = shared.clone();
+    engine.register_fn(
+        "freeze",
+        move |address: INT, value: INT| -> Result<(), Box<EvalAltResult>> {
+            let mut shared = s.borrow_mut();
+            let address = shared.address(address)?;
+            let value = byte(value)?;
+            shared.frozen.add(Cheat { address, value }, "");
+            Ok(())
+        },
+    );
+    let s = shared.clone();
+    engine.register_fn("unfreeze", move |address: INT| {
+        if let Ok(address) = usize::try_from(address) {
+            s.borrow_mut().frozen.remove(address);
+        }
+    });
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // counts V0 up forever, with a wait for a key into V1 at 0x206
+    const ROM: [u8; 10] = [
+        0x70, 0x01, // V0 += 1
+        0xa3, 0x00, // I = 0x300
+        0xf0, 0x55, // store V0 at 0x300
+        0xf1, 0x0a, // wait for a key into V1
+        0x12, 0x00, // loop
+    ];
+
+    fn new_script(source: &str) -> (Script, Processor) {
+        let mut processor = Processor::new();
+        processor.load_game(&ROM);
+        let script = Script::new(source, &mut processor).unwrap();
+        (script, processor)
+    }
+
+    #[test]
+    fn test_peek_poke() {
+        let (mut script, mut processor) = new_script(
+            "fn on_frame() { poke(0x400, peek(0x200) + frame()); set_reg(5, reg(0) * 2); }",
+        );
+        script.run_frame(&mut processor, 10);
+        assert_eq!(processor.memory()[0x400], 0x71);
+        assert_eq!(processor.registers()[5], 2);
+    }
+
+    #[test]
+    fn test_on_pc() {
+        let (mut script, mut processor) = new_script(
+            "break_at(0x206);
+             fn on_pc(pc) { this.hits = (this.hits ?? 0) + 1; show(`hit ${this.hits} at ${pc}`); }",
+        );
+        // waiting at 0x206 doesn't count as another hit
+        script.run_frame(&mut processor, 10);
+        script.run_frame(&mut processor, 10);
+        assert_eq!(script.overlay(), Some("hit 1 at 518".to_string()));
+    }
+
+    #[test]
+    fn test_on_write() {
+        let (mut script, mut processor) = new_script(
+            "watch(0x300);
+             fn on_write(address, value) { poke(0x301, value); }",
+        );
+        script.run_frame(&mut processor, 10);
+        assert_eq!(processor.memory()[0x301], 1);
+    }
+
+    #[test]
+    fn test_keys() {
+        // taps key 9 over two frames, the rom waits for it
+        let (mut script, mut processor) = new_script(
+            "fn on_frame() { if frame() == 1 { press(9) } else if frame() == 2 { release(9) } }
+             fn on_key(key, pressed) { show(`${key} ${pressed}`) }",
+        );
+        script.run_frame(&mut processor, 10);
+        script.run_frame(&mut processor, 10);
+        assert_eq!(processor.registers()[1], 9);
+
+        processor.key_event(KeyEvent::Pressed(2));
+        script.key_event(&mut processor, KeyEvent::Pressed(2));
+        assert_eq!(script.overlay(), Some("2 true".to_string()));
+    }
+
+    #[test]
+    fn test_search() {
+        // only the copy of V0 at 0x300 goes up from 0 to 1
+        let (mut script, mut processor) = new_script(
+            "search_start();
+             fn on_frame() {
+                 if frame() == 1 { search(\"increased\"); search(\"equal\", 1); }
+                 else { freeze(results()[0], 42); }
+             }",
+        );
+        script.run_frame(&mut processor, 10);
+        assert_eq!(processor.memory()[0x300], 1);
+        script.run_frame(&mut processor, 10);
+        script.run_frame(&mut processor, 10);
+        assert_eq!(processor.memory()[0x300], 42);
+    }
+
+    #[test]
+    fn test_on_load() {
+        let mut processor = Processor::new();
+        let mut script = Script::new(
+            "show(`${peek(0x200)}`);
+             fn on_load() { show(`${peek(0x200)}`); }",
+            &mut processor,
+        )
+        .unwrap();
+        // the top level ran before the rom was there
+        assert_eq!(script.overlay(), Some("0".to_string()));
+        processor.load_game(&ROM);
+        script.load(&mut processor);
+        assert_eq!(script.overlay(), Some("112".to_string()));
+        assert_eq!(processor.memory()[0x200], 0x70);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut processor = Processor::new();
+        assert!(matches!(
+            Script::new("fn (", &mut processor),
+            Err(ScriptError::Parse(_))
+        ));
+        assert!(matches!(
+            Script::new("poke(0x10000, 1)", &mut processor),
+            Err(ScriptError::Eval(_))
+        ));
+
+        // errors in callbacks stop the script, the emulation goes on
+        let (mut script, mut processor) = new_script("fn on_frame() { set_reg(0, 300); }");
+        script.run_frame(&mut processor, 10);
+        assert!(script.failed);
+        assert_eq!(processor.registers()[0], 1);
+    }
+}
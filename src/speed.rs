@@ -0,0 +1,138 @@
+use crate::input::InputEvent;
+
+// selectable speeds as frames emulated per frame shown, e.g. (1, 2) runs every second frame
+const SPEEDS: [(u32, u32); 5] = [(1, 4), (1, 2), (1, 1), (2, 1), (4, 1)];
+const NORMAL: usize = 2;
+
+// Pause, frame advance, slow motion and fast forward
+pub struct Speed {
+    level: usize,       // index into SPEEDS
+    fast_forward: bool, // as fast as possible
+    paused: bool,
+    advance: bool, // run a single frame while paused
+    ticks: u32,    // frames shown since the speed changed
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Speed {
+    pub fn new() -> Self {
+        Speed {
+            level: NORMAL,
+            fast_forward: false,
+            paused: false,
+            advance: false,
+            ticks: 0,
+        }
+    }
+
+    // apply a hotkey, false if the event is none of ours
+    pub fn handle(&mut self, input_event: InputEvent) -> bool {
+        match input_event {
+            InputEvent::TogglePause => {
+                self.paused = !self.paused;
+                self.advance = false;
+            }
+            InputEvent::FrameAdvance => {
+                self.paused = true;
+                self.advance = true;
+            }
+            InputEvent::SpeedUp => self.level = (self.level + 1).min(SPEEDS.len() - 1),
+            InputEvent::SpeedDown => self.level = self.level.saturating_sub(1),
+            InputEvent::ToggleFastForward => self.fast_forward = !self.fast_forward,
+            _ => return false,
+        }
+        self.ticks = 0;
+        true
+    }
+
+    // pause or resume no matter the current state, for remote control
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.advance = false;
+        self.ticks = 0;
+    }
+
+    // frames to emulate during the next frame shown
+    pub fn frames(&mut self) -> u32 {
+        if self.paused {
+            let advance = self.advance;
+            self.advance = false;
+            return advance as u32;
+        }
+        let (frames, every) = SPEEDS[self.level];
+        let due = self.ticks % every == 0;
+        self.ticks = self.ticks.wrapping_add(1);
+        if due {
+            frames
+        } else {
+            0
+        }
+    }
+
+    // keep emulating until the frame time is up
+    pub fn is_uncapped(&self) -> bool {
+        self.fast_forward && !self.paused
+    }
+
+    // what to show on screen, nothing at normal speed
+    pub fn label(&self) -> Option<String> {
+        if self.paused {
+            return Some("PAUSE".to_string());
+        }
+        if self.fast_forward {
+            return Some("MAX".to_string());
+        }
+        match SPEEDS[self.level] {
+            (1, 1) => None,
+            (frames, 1) => Some(format!("{}X", frames)),
+            (_, every) => Some(format!("1/{}X", every)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pause_and_advance() {
+        let mut speed = Speed::new();
+        assert_eq!(speed.frames(), 1);
+        assert_eq!(speed.label(), None);
+
+        speed.handle(InputEvent::TogglePause);
+        assert_eq!(speed.frames(), 0);
+        assert_eq!(speed.label(), Some("PAUSE".to_string()));
+
+        // exactly one frame per press
+        speed.handle(InputEvent::FrameAdvance);
+        assert_eq!(speed.frames(), 1);
+        assert_eq!(speed.frames(), 0);
+
+        speed.handle(InputEvent::TogglePause);
+        assert_eq!(speed.frames(), 1);
+    }
+
+    #[test]
+    fn test_speed_levels() {
+        let mut speed = Speed::new();
+        speed.handle(InputEvent::SpeedUp);
+        assert_eq!(speed.frames(), 2);
+        assert_eq!(speed.label(), Some("2X".to_string()));
+
+        speed.handle(InputEvent::SpeedDown);
+        speed.handle(InputEvent::SpeedDown);
+        let frames: Vec<u32> = (0..4).map(|_| speed.frames()).collect();
+        assert_eq!(frames, vec![1, 0, 1, 0]);
+        assert_eq!(speed.label(), Some("1/2X".to_string()));
+
+        speed.handle(InputEvent::ToggleFastForward);
+        assert!(speed.is_uncapped());
+        assert_eq!(speed.label(), Some("MAX".to_string()));
+    }
+}
@@ -0,0 +1,84 @@
+// Helpers for the binary save state format, all numbers are little endian
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum StateError {
+    WrongMagic,
+    UnsupportedVersion(u8),
+    Truncated,
+    MemorySize { state: usize, processor: usize }, // saved with a different platform
+    Invalid(&'static str),                         // a value out of range
+}
+
+pub struct Writer {
+    data: Vec<u8>,
+}
+
+impl Writer {
+    pub fn new(magic: &[u8], version: u8) -> Self {
+        let mut data = magic.to_vec();
+        data.push(version);
+        Writer { data }
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+
+    pub fn u32(&mut self, value: usize) {
+        self.data.extend((value as u32).to_le_bytes());
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend(bytes);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    // error unless data starts with magic and version
+    pub fn new(data: &'a [u8], magic: &[u8], version: u8) -> Result<Self, StateError> {
+        if !data.starts_with(magic) {
+            return Err(StateError::WrongMagic);
+        }
+        let mut reader = Reader {
+            data: &data[magic.len()..],
+        };
+        match reader.u8()? {
+            found if found == version => Ok(reader),
+            found => Err(StateError::UnsupportedVersion(found)),
+        }
+    }
+
+    pub fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, StateError> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u32(&mut self) -> Result<usize, StateError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.data.len() < len {
+            return Err(StateError::Truncated);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+}
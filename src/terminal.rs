@@ -0,0 +1,286 @@
+use std::io::{self, Stdout, Write};
+use std::time::Duration;
+
+use crossterm::event::{
+    self, Event, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::{cursor, execute, queue, terminal};
+
+use crate::filter::{Filter, Phosphor};
+use crate::frontend::Frontend;
+use crate::input::{Controls, InputEvent, KeyEvent};
+use crate::palette::Palette;
+use crate::processor::Processor;
+
+// most terminals only report presses, so a key counts as held for a few frames
+const HOLD_FRAMES: u8 = 10;
+
+// no colour has this value, so these pixels always get drawn
+const UNDRAWN: u32 = u32::MAX;
+
+// Draws the screen with unicode half blocks, two chip8 rows per terminal line
+pub struct Terminal {
+    stdout: Stdout,
+    palette: Palette,
+    phosphor: Phosphor,
+    colors: Vec<u32>,
+    drawn: Vec<u32>,    // colours currently shown, to only send changes
+    panel: Vec<String>, // register lines currently shown
+    controls: Controls,
+    release_events: bool, // terminal reports key releases
+    held: [u8; 16],       // frames left until a key counts as released
+    status: Option<String>,
+}
+
+impl Terminal {
+    pub fn new(
+        palette: Palette,
+        filter: Filter,
+        controls: Controls,
+        title: &str,
+    ) -> io::Result<Self> {
+        let mut stdout = io::stdout();
+
+        terminal::enable_raw_mode()?;
+        execute!(
+            stdout,
+            terminal::EnterAlternateScreen,
+            terminal::SetTitle(title),
+            cursor::Hide
+        )?;
+
+        // ask for release events, where the terminal supports them
+        let release_events = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if release_events {
+            execute!(
+                stdout,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+
+        Ok(Terminal {
+            stdout,
+            palette,
+            phosphor: Phosphor::new(filter),
+            colors: vec![0; crate::SCREEN_WIDTH * crate::SCREEN_HEIGHT],
+            drawn: vec![UNDRAWN; crate::SCREEN_WIDTH * crate::SCREEN_HEIGHT],
+            panel: Vec::new(),
+            controls,
+            release_events,
+            held: [0; 16],
+            status: None,
+        })
+    }
+
+    fn draw_registers(&mut self, processor: &Processor) -> io::Result<()> {
+        let registers = processor.registers();
+        let (delay_timer, sound_timer) = processor.timers();
+
+        let mut panel: Vec<String> = (0..8)
+            .map(|row| {
+                format!(
+                    "V{:X} {:02X}  V{:X} {:02X}",
+                    row,
+                    registers[row],
+                    row + 8,
+                    registers[row + 8]
+                )
+            })
+            .collect();
+        panel.push(String::new());
+        panel.push(format!(
+            "PC {:03X}  I {:03X}",
+            processor.pc(),
+            processor.index()
+        ));
+        panel.push(format!("DT {:02X}   ST {:02X}", delay_timer, sound_timer));
+        panel.push(String::new());
+        panel.push(format!("{:<11}", self.status.as_deref().unwrap_or("")));
+        panel.push("Esc to quit".to_string());
+
+        // only send lines, which changed
+        let column = crate::SCREEN_WIDTH as u16 + 2;
+        queue!(self.stdout, ResetColor)?;
+        for (row, line) in panel.iter().enumerate() {
+            if self.panel.get(row) != Some(line) {
+                queue!(self.stdout, cursor::MoveTo(column, row as u16), Print(line))?;
+            }
+        }
+        self.panel = panel;
+        Ok(())
+    }
+
+    fn draw_screen(&mut self, processor: &Processor) -> io::Result<()> {
+        self.phosphor
+            .apply(processor.screen(), &self.palette, &mut self.colors);
+
+        for line in 0..crate::SCREEN_HEIGHT / 2 {
+            for x in 0..crate::SCREEN_WIDTH {
+                let top = line * 2 * crate::SCREEN_WIDTH + x;
+                let bottom = top + crate::SCREEN_WIDTH;
+                if self.colors[top] == self.drawn[top] && self.colors[bottom] == self.drawn[bottom]
+                {
+                    continue;
+                }
+                // upper half is the foreground, lower half the background
+                queue!(
+                    self.stdout,
+                    cursor::MoveTo(x as u16, line as u16),
+                    SetForegroundColor(to_color(self.colors[top])),
+                    SetBackgroundColor(to_color(self.colors[bottom])),
+                    Print('▀')
+                )?;
+            }
+        }
+        self.drawn.copy_from_slice(&self.colors);
+        Ok(())
+    }
+}
+
+impl Frontend for Terminal {
+    fn fetch(&mut self) -> Result<Vec<InputEvent>, ()> {
+        let mut input_events = Vec::new();
+
+        // release keys, which weren't repeated for a while
+        if !self.release_events {
+            for (key, frames) in self.held.iter_mut().enumerate() {
+                if *frames == 1 {
+                    input_events.push(InputEvent::Key(KeyEvent::Released(key)));
+                }
+                *frames = frames.saturating_sub(1);
+            }
+        }
+
+        while event::poll(Duration::from_secs(0)).map_err(|_| ())? {
+            match event::read().map_err(|_| ())? {
+                Event::Key(key) => {
+                    // error if the user wants to quit
+                    let ctrl_c = key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(KeyModifiers::CONTROL);
+                    if key.code == KeyCode::Esc || ctrl_c {
+                        return Err(());
+                    }
+
+                    if let Some(hotkey) = hotkey(key.code) {
+                        if key.kind == KeyEventKind::Press {
+                            input_events.push(hotkey);
+                        }
+                        continue;
+                    }
+
+                    let index = match key.code {
+                        KeyCode::Up => self.controls.up,
+                        KeyCode::Down => self.controls.down,
+                        KeyCode::Left => self.controls.left,
+                        KeyCode::Right => self.controls.right,
+                        KeyCode::Char(' ') => self.controls.a,
+                        KeyCode::Enter => self.controls.b,
+                        KeyCode::Char(c) => keymap(c.to_ascii_lowercase()),
+                        _ => None,
+                    };
+                    if let Some(index) = index {
+                        match key.kind {
+                            KeyEventKind::Release => {
+                                input_events.push(InputEvent::Key(KeyEvent::Released(index)));
+                            }
+                            KeyEventKind::Repeat if self.release_events => {}
+                            _ => {
+                                if self.held[index] == 0 {
+                                    input_events.push(InputEvent::Key(KeyEvent::Pressed(index)));
+                                }
+                                if !self.release_events {
+                                    self.held[index] = HOLD_FRAMES;
+                                }
+                            }
+                        }
+                    }
+                }
+                Event::Resize(..) => {
+                    let _ = execute!(self.stdout, terminal::Clear(terminal::ClearType::All));
+                    self.drawn.fill(UNDRAWN);
+                    self.panel.clear();
+                    input_events.push(InputEvent::Redraw);
+                }
+                _ => {}
+            }
+        }
+        Ok(input_events)
+    }
+
+    fn draw(&mut self, processor: &Processor) {
+        let _ = self
+            .draw_screen(processor)
+            .and_then(|_| self.draw_registers(processor))
+            .and_then(|_| self.stdout.flush());
+    }
+
+    fn is_animated(&self) -> bool {
+        // keep the registers up to date
+        true
+    }
+
+    fn set_status(&mut self, status: Option<String>) {
+        self.status = status;
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if self.release_events {
+            let _ = execute!(self.stdout, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(
+            self.stdout,
+            ResetColor,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// same layout as the sdl frontend
+fn keymap(key: char) -> Option<usize> {
+    match key {
+        '1' => Some(0x1),
+        '2' => Some(0x2),
+        '3' => Some(0x3),
+        '4' => Some(0xc),
+        'q' => Some(0x4),
+        'w' => Some(0x5),
+        'e' => Some(0x6),
+        'r' => Some(0xd),
+        'a' => Some(0x7),
+        's' => Some(0x8),
+        'd' => Some(0x9),
+        'f' => Some(0xe),
+        'z' => Some(0xa),
+        'x' => Some(0x0),
+        'c' => Some(0xb),
+        'v' => Some(0xf),
+        _ => None,
+    }
+}
+
+// same emulator controls as the sdl frontend
+fn hotkey(key: KeyCode) -> Option<InputEvent> {
+    match key {
+        KeyCode::Char('p') | KeyCode::Pause => Some(InputEvent::TogglePause),
+        KeyCode::Char('n') => Some(InputEvent::FrameAdvance),
+        KeyCode::Char(']') => Some(InputEvent::SpeedUp),
+        KeyCode::Char('[') => Some(InputEvent::SpeedDown),
+        KeyCode::Tab => Some(InputEvent::ToggleFastForward),
+        KeyCode::F(5) => Some(InputEvent::Reset),
+        _ => None,
+    }
+}
+
+fn to_color(rgb: u32) -> Color {
+    Color::Rgb {
+        r: (rgb >> 16) as u8,
+        g: (rgb >> 8) as u8,
+        b: rgb as u8,
+    }
+}
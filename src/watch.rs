@@ -0,0 +1,91 @@
+use std::fs;
+use std::time::SystemTime;
+
+use crate::cartridge::Cartridge;
+
+// frames between looking at the file, twice a second
+const POLL_FRAMES: u32 = 30;
+
+// Reloads a rom, whenever its file changes
+pub struct Watcher {
+    filename: String,
+    entry: Option<String>,
+    load_address: usize, // octo cartridges get assembled for it
+    max_size: usize,     // bigger roms don't fit into memory and are ignored
+    modified: Option<SystemTime>,
+    frames: u32, // frames until the next look
+}
+
+impl Watcher {
+    pub fn new(filename: &str, entry: Option<&str>, load_address: usize, max_size: usize) -> Self {
+        Watcher {
+            filename: filename.to_string(),
+            entry: entry.map(String::from),
+            load_address,
+            max_size,
+            modified: modified(filename),
+            frames: POLL_FRAMES,
+        }
+    }
+
+    // called once per frame, the new rom if the file changed
+    pub fn poll(&mut self) -> Option<Vec<u8>> {
+        self.frames -= 1;
+        if self.frames > 0 {
+            return None;
+        }
+        self.frames = POLL_FRAMES;
+
+        let modified = modified(&self.filename);
+        if modified == self.modified {
+            return None;
+        }
+
+        // a half written file fails to load, so try again next time
+        let mut cartridge = Cartridge::new(&self.filename, self.entry.as_deref()).ok()?;
+        cartridge.relocate(self.load_address).ok()?;
+        cartridge.validate(self.max_size).ok()?;
+        self.modified = modified;
+        Some(cartridge.data().to_vec())
+    }
+}
+
+fn modified(filename: &str) -> Option<SystemTime> {
+    fs::metadata(filename)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::File;
+    use std::time::Duration;
+
+    fn poll_frames(watcher: &mut Watcher) -> Option<Vec<u8>> {
+        (0..POLL_FRAMES).filter_map(|_| watcher.poll()).last()
+    }
+
+    #[test]
+    fn test_reload_on_change() {
+        let path = env::temp_dir().join("chip8-rs-watch.ch8");
+        let filename = path.to_str().unwrap();
+        fs::write(&path, [0x12, 0x00]).unwrap();
+
+        let mut watcher = Watcher::new(filename, None, 0x200, 3584);
+        assert_eq!(poll_frames(&mut watcher), None);
+
+        fs::write(&path, [0x00, 0xe0, 0x12, 0x02]).unwrap();
+        // file systems might not notice such a quick change otherwise
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+
+        assert_eq!(
+            poll_frames(&mut watcher),
+            Some(vec![0x00, 0xe0, 0x12, 0x02])
+        );
+        assert_eq!(poll_frames(&mut watcher), None);
+    }
+}